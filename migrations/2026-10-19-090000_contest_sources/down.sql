@@ -0,0 +1,5 @@
+DELETE FROM contests WHERE phiquadro_id IS NULL OR phiquadro_sess IS NULL;
+
+ALTER TABLE contests
+    ALTER COLUMN phiquadro_id SET NOT NULL,
+    ALTER COLUMN phiquadro_sess SET NOT NULL;
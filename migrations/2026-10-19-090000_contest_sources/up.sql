@@ -0,0 +1,3 @@
+ALTER TABLE contests
+    ALTER COLUMN phiquadro_id DROP NOT NULL,
+    ALTER COLUMN phiquadro_sess DROP NOT NULL;
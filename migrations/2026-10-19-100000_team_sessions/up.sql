@@ -0,0 +1 @@
+ALTER TABLE teams ADD COLUMN phiquadro_sess INTEGER;
@@ -0,0 +1,2 @@
+ALTER TABLE contests DROP COLUMN hidden_drift;
+ALTER TABLE teams DROP COLUMN hidden;
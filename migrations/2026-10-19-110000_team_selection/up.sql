@@ -0,0 +1,2 @@
+ALTER TABLE teams ADD COLUMN hidden BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE contests ADD COLUMN hidden_drift BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1 @@
+ALTER TABLE contests ADD COLUMN time_scale DOUBLE PRECISION NOT NULL DEFAULT 1;
@@ -0,0 +1 @@
+ALTER TABLE contests ADD COLUMN paused_at TIMESTAMPTZ;
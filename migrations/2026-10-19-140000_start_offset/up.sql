@@ -0,0 +1 @@
+ALTER TABLE contests ADD COLUMN start_offset INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1 @@
+ALTER TABLE teams ADD COLUMN is_bot BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,3 @@
+ALTER TABLE contests DROP COLUMN leaderboard_hide_owner;
+ALTER TABLE contests DROP COLUMN leaderboard_hide_teams;
+ALTER TABLE contests DROP COLUMN leaderboard;
@@ -0,0 +1,3 @@
+ALTER TABLE contests ADD COLUMN leaderboard BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE contests ADD COLUMN leaderboard_hide_teams BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE contests ADD COLUMN leaderboard_hide_owner BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,3 @@
+ALTER TABLE contests DROP COLUMN spectator_token;
+ALTER TABLE contests DROP COLUMN revealed;
+ALTER TABLE contests DROP COLUMN freeze_time;
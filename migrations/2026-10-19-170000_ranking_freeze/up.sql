@@ -0,0 +1,3 @@
+ALTER TABLE contests ADD COLUMN freeze_time INTEGER;
+ALTER TABLE contests ADD COLUMN revealed BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE contests ADD COLUMN spectator_token VARCHAR(64) UNIQUE;
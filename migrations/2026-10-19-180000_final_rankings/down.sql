@@ -0,0 +1,2 @@
+DROP TABLE final_ranking_teams;
+DROP TABLE final_rankings;
@@ -0,0 +1,27 @@
+CREATE TABLE final_rankings (
+    contest_id      INTEGER PRIMARY KEY NOT NULL REFERENCES contests(id),
+    computed_at     TIMESTAMP WITH TIME ZONE NOT NULL,
+    question_scores INTEGER[] NOT NULL CHECK (array_position(question_scores, NULL) IS NULL),
+    question_locked BOOLEAN[] NOT NULL CHECK (array_position(question_locked, NULL) IS NULL)
+);
+
+-- The teams are copied, so that deleting or renaming them doesn't alter the results
+CREATE TABLE final_ranking_teams (
+    id                  INTEGER PRIMARY KEY NOT NULL GENERATED ALWAYS AS IDENTITY,
+    contest_id          INTEGER NOT NULL REFERENCES final_rankings(contest_id) ON DELETE CASCADE,
+    rank                INTEGER NOT NULL,
+    team_id             INTEGER NOT NULL,
+    team_name           VARCHAR(255) NOT NULL,
+    is_fake             BOOLEAN NOT NULL,
+    is_bot              BOOLEAN NOT NULL,
+    phiquadro_sess      INTEGER,
+    score               INTEGER NOT NULL,
+    question_scores     INTEGER[] NOT NULL CHECK (array_position(question_scores, NULL) IS NULL),
+    question_statuses   SMALLINT[] NOT NULL CHECK (array_position(question_statuses, NULL) IS NULL),
+    jollies             BOOLEAN[] NOT NULL CHECK (array_position(jollies, NULL) IS NULL),
+
+    UNIQUE (contest_id, rank),
+    CONSTRAINT positive_rank CHECK (rank > 0)
+);
+
+CREATE INDEX ON final_ranking_teams (contest_id);
@@ -0,0 +1,3 @@
+ALTER TABLE jollies DROP COLUMN idempotency_key;
+
+ALTER TABLE submissions DROP COLUMN idempotency_key;
@@ -0,0 +1,5 @@
+ALTER TABLE submissions ADD COLUMN idempotency_key VARCHAR(255);
+ALTER TABLE submissions ADD UNIQUE (team_id, idempotency_key);
+
+ALTER TABLE jollies ADD COLUMN idempotency_key VARCHAR(255);
+ALTER TABLE jollies ADD UNIQUE (team_id, idempotency_key);
@@ -0,0 +1,2 @@
+ALTER TABLE final_ranking_teams DROP COLUMN is_ghost;
+ALTER TABLE teams DROP COLUMN is_ghost;
@@ -0,0 +1,2 @@
+ALTER TABLE teams ADD COLUMN is_ghost BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE final_ranking_teams ADD COLUMN is_ghost BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,86 @@
+use rocket::http::{ContentType, Header, HeaderMap, Status};
+use rocket_db_pools::Connection;
+
+use crate::api::{ApiError, ApiResponse, ApiUser};
+use crate::contest::export::{fetch_ranking_csv, fetch_submissions_csv};
+use crate::contest::printouts::{fetch_results_pdf, fetch_results_zip};
+use crate::error::IntoStatusResult;
+use crate::DB;
+
+/// A file which the browser downloads instead of showing it
+#[derive(Responder)]
+pub struct Attachment {
+    body: Vec<u8>,
+    content_type: ContentType,
+    disposition: Header<'static>,
+}
+
+impl Attachment {
+    fn new(body: Vec<u8>, content_type: ContentType, file_name: String) -> Self {
+        Self {
+            body,
+            content_type,
+            disposition: Header::new("Content-Disposition", format!("attachment; filename=\"{}\"", file_name)),
+        }
+    }
+}
+
+fn not_found<'r>() -> ApiResponse<'r, ApiError> {
+    ApiResponse {
+        status: Status::NotFound,
+        body: ApiError { error: "La gara non esiste o non ti appartiene".to_string() },
+        headers: HeaderMap::new(),
+    }
+}
+
+#[get("/contests/<id>/export/ranking.csv")]
+pub async fn get_ranking_csv<'r>(
+    id: i32,
+    mut db: Connection<DB>,
+    api_user: ApiUser,
+) -> Result<Attachment, ApiResponse<'r, ApiError>> {
+    match fetch_ranking_csv(&mut db, api_user.user_id, id)
+        .await
+        .attach_info(Status::InternalServerError, "Errore riscontrato durante l'esportazione")?
+    {
+        Some(csv) => Ok(Attachment::new(csv.into_bytes(), ContentType::CSV, format!("classifica_{}.csv", id))),
+        None => Err(not_found()),
+    }
+}
+
+#[get("/contests/<id>/export/submissions.csv")]
+pub async fn get_submissions_csv<'r>(
+    id: i32,
+    mut db: Connection<DB>,
+    api_user: ApiUser,
+) -> Result<Attachment, ApiResponse<'r, ApiError>> {
+    match fetch_submissions_csv(&mut db, api_user.user_id, id)
+        .await
+        .attach_info(Status::InternalServerError, "Errore riscontrato durante l'esportazione")?
+    {
+        Some(csv) => Ok(Attachment::new(csv.into_bytes(), ContentType::CSV, format!("risposte_{}.csv", id))),
+        None => Err(not_found()),
+    }
+}
+
+#[get("/contests/<id>/export/results.pdf")]
+pub async fn get_results_pdf<'r>(
+    id: i32,
+    mut db: Connection<DB>,
+    api_user: ApiUser,
+) -> Result<Attachment, ApiResponse<'r, ApiError>> {
+    let pdf = fetch_results_pdf(&mut db, api_user.user_id, id).await?;
+
+    Ok(Attachment::new(pdf, ContentType::PDF, format!("risultati_{}.pdf", id)))
+}
+
+#[get("/contests/<id>/export/results.zip")]
+pub async fn get_results_zip<'r>(
+    id: i32,
+    mut db: Connection<DB>,
+    api_user: ApiUser,
+) -> Result<Attachment, ApiResponse<'r, ApiError>> {
+    let zip = fetch_results_zip(&mut db, api_user.user_id, id).await?;
+
+    Ok(Attachment::new(zip, ContentType::ZIP, format!("risultati_{}.zip", id)))
+}
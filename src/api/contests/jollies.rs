@@ -8,9 +8,9 @@ use rocket_db_pools::diesel::prelude::RunQueryDsl;
 use rocket_db_pools::Connection;
 use serde::{Deserialize, Serialize};
 
-use crate::api::{ApiError, ApiInputResult, ApiResponse, ApiUser};
+use crate::api::{prop_error, ApiError, ApiInputResult, ApiResponse, ApiUser, IdempotencyKey};
 use crate::error::IntoStatusResult;
-use crate::model::Jolly;
+use crate::model::{contest_to_real_time, Jolly};
 use crate::DB;
 
 #[derive(Serialize)]
@@ -82,10 +82,58 @@ pub async fn get_jollies<'r>(
     })
 }
 
+/// Looks for the jolly a team chose with an idempotency key, returning its id and question
+async fn fetch_keyed_jolly<'r>(
+    db: &mut Connection<DB>,
+    team_id: i32,
+    key: &str,
+) -> Result<Option<(i32, i32)>, ApiResponse<'r, ApiError>> {
+    use crate::schema::jollies;
+
+    let jolly = jollies::dsl::jollies
+        .select((jollies::id, jollies::question_id))
+        .filter(jollies::team_id.eq(team_id))
+        .filter(jollies::idempotency_key.eq(key))
+        .load::<(i32, i32)>(db)
+        .await
+        .attach_info(Status::InternalServerError, "Errore incontrato durante l'invio del jolly")?;
+
+    Ok(jolly.into_iter().next())
+}
+
+/// Answers a retried request with what was answered when the jolly was first chosen
+fn replay_jolly<'r>(
+    id: i32,
+    jolly: &JollyPostData,
+    (jolly_id, question_id): (i32, i32),
+) -> Result<ApiResponse<'r, JollyPostResponse>, ApiResponse<'r, ApiError>> {
+    if question_id != jolly.question_id {
+        return Err(ApiResponse {
+            status: Status::UnprocessableEntity,
+            body: ApiError { error: "La chiave di idempotenza è già stata usata per un altro jolly".to_string() },
+            headers: HeaderMap::new(),
+        });
+    }
+
+    Ok(created_jolly(id, jolly_id))
+}
+
+fn created_jolly<'r>(id: i32, jolly_id: i32) -> ApiResponse<'r, JollyPostResponse> {
+    let mut headers = HeaderMap::new();
+    headers.add(Header::new(header::LOCATION.as_str(), format!("/contest/{id}/jollies/{jolly_id}")));
+
+    ApiResponse {
+        status: Status::Created,
+        body: JollyPostResponse { jolly_id },
+        headers,
+    }
+}
+
 #[post("/contests/<id>/jollies", format = "application/json", data = "<jolly>")]
 pub async fn post_jolly<'r>(
     id: i32,
     jolly: ApiInputResult<'_, JollyPostData>,
+    key: IdempotencyKey,
     mut db: Connection<DB>,
     api_user: ApiUser,
 ) -> Result<ApiResponse<'r, JollyPostResponse>, ApiResponse<'r, ApiError>> {
@@ -98,16 +146,24 @@ pub async fn post_jolly<'r>(
             headers: HeaderMap::new(),
         });
     };
+    key.validate()?;
 
     let contest = contests::dsl::contests
-        .select((contests::owner_id, contests::start_time, contests::jolly_time))
+        .select((
+            contests::owner_id,
+            contests::start_time,
+            contests::jolly_time,
+            contests::time_scale,
+            contests::paused_at,
+            contests::start_offset,
+        ))
         .filter(contests::id.eq(id))
         .filter(contests::active.eq(true))
-        .load::<(i32, DateTime<Utc>, i32)>(&mut **db)
+        .load::<(i32, DateTime<Utc>, i32, f64, Option<DateTime<Utc>>, i32)>(&mut **db)
         .await
         .attach_info(Status::InternalServerError, "Errore incontrato durante l'invio del jolly")?;
 
-    let Some(&(contest_owner, start, jolly_time)) = contest.get(0) else {
+    let Some(&(contest_owner, start, jolly_time, time_scale, paused_at, start_offset)) = contest.get(0) else {
         return Err(ApiResponse {
             status: Status::NotFound,
             body: ApiError { error: "La gara non esiste o non ti appartiene".to_string() },
@@ -156,8 +212,29 @@ pub async fn post_jolly<'r>(
         });
     };
 
+    // A retried request is answered as the first time, even if meanwhile the time for the jolly has run out
+    if let Some(key) = &key.0 {
+        if let Some(chosen) = fetch_keyed_jolly(&mut db, jolly.team_id, key).await? {
+            return replay_jolly(id, &jolly, chosen);
+        }
+    }
+
+    // Joining after the time for the jolly is over, the user teams cannot choose it at all
+    if start_offset >= jolly_time {
+        return Err(ApiResponse {
+            status: Status::Forbidden,
+            body: ApiError {
+                error: "Il tempo per la consegna del jolly scade prima del minuto di partenza".to_string(),
+            },
+            headers: HeaderMap::new(),
+        });
+    }
+
+    // User teams can only play after joining the contest
+    let join = start + contest_to_real_time(Duration::seconds(start_offset as i64), time_scale);
+
     let curr_time = chrono::Utc::now();
-    if curr_time < start {
+    if curr_time < join {
         return Err(ApiResponse {
             status: Status::Forbidden,
             body: ApiError { error: "La gara non è ancora iniziata".to_string() },
@@ -165,7 +242,16 @@ pub async fn post_jolly<'r>(
         });
     }
 
-    if curr_time > start + Duration::seconds(jolly_time as i64) {
+    if paused_at.is_some() {
+        return Err(ApiResponse {
+            status: Status::Forbidden,
+            body: ApiError { error: "La gara è in pausa".to_string() },
+            headers: HeaderMap::new(),
+        });
+    }
+
+    // The time for the jolly is counted from the start of the contest, as for the teams of the original one
+    if curr_time > start + contest_to_real_time(Duration::seconds(jolly_time as i64), time_scale) {
         return Err(ApiResponse {
             status: Status::Forbidden,
             body: ApiError { error: "Il tempo per la consegna del jolly è scaduto".to_string() },
@@ -173,25 +259,34 @@ pub async fn post_jolly<'r>(
         });
     }
 
-    let jolly_id = diesel::insert_into(jollies::dsl::jollies)
-        .values(Jolly {
-            sub_time: chrono::Utc::now(),
-            team_id: jolly.team_id,
-            question_id: jolly.question_id,
-        })
+    let inserted = diesel::insert_into(jollies::dsl::jollies)
+        .values((
+            Jolly {
+                sub_time: chrono::Utc::now(),
+                team_id: jolly.team_id,
+                question_id: jolly.question_id,
+            },
+            jollies::idempotency_key.eq(&key.0),
+        ))
         .returning(jollies::id)
         .get_result(&mut **db)
-        .await
-        .attach_info(Status::Forbidden, "Non puoi scegliere due volte il jolly!")?;
+        .await;
 
-    let mut headers = HeaderMap::new();
-    headers.add(Header::new(header::LOCATION.as_str(), format!("/contest/{id}/jollies/{jolly_id}")));
+    let jolly_id = match inserted {
+        Ok(jolly_id) => jolly_id,
+        Err(err) => {
+            // The same request may have been retried while this one was running
+            if let Some(key) = &key.0 {
+                if let Some(chosen) = fetch_keyed_jolly(&mut db, jolly.team_id, key).await? {
+                    return replay_jolly(id, &jolly, chosen);
+                }
+            }
 
-    Ok(ApiResponse {
-        status: Status::Created,
-        body: JollyPostResponse { jolly_id },
-        headers,
-    })
+            return Err(prop_error(err, Status::Forbidden, "Non puoi scegliere due volte il jolly!"));
+        }
+    };
+
+    Ok(created_jolly(id, jolly_id))
 }
 
 #[get("/contests/<id>/jollies/<jolly_id>")]
@@ -1,22 +1,35 @@
 use anyhow::anyhow;
-use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use chrono::{DateTime, NaiveDateTime, TimeDelta, TimeZone, Utc};
 use chrono_tz::Europe::Rome;
-use diesel::sql_types::{Integer, Interval};
+use diesel::sql_types::{Double, Integer, Timestamptz};
 use diesel::{sql_query, update, ExpressionMethods, QueryDsl};
 use diesel::prelude::{AsChangeset, Queryable};
 use reqwest::header;
 use rocket::http::{Header, HeaderMap, Status};
 use rocket::State;
-use rocket_db_pools::{diesel::prelude::RunQueryDsl, Connection};
-use serde::{Deserialize, Serialize};
+use rocket_db_pools::diesel::prelude::{AsyncConnection, RunQueryDsl};
+use rocket_db_pools::diesel::scoped_futures::ScopedFutureExt;
+use rocket_db_pools::Connection;
+use serde::{Deserialize, Deserializer, Serialize};
 
-use crate::model::timedelta_to_pg_interval;
-use crate::{PhiQuadroLogin, DB};
-use crate::contest::import::create_contest;
+use crate::model::contest_to_real_time;
+use crate::{Config, DB};
+use crate::contest::import::{
+    create_contest, create_custom_contest, import_contest, ContestSettings, MAX_TIME_SCALE, MIN_TIME_SCALE,
+};
+use crate::contest::interchange::{ContestDocument, JsonTeam};
+use crate::contest::selection::TeamSelection;
+use crate::contest::leaderboard::{fetch_leaderboard, set_leaderboard_settings, Leaderboard, LeaderboardSettings};
+use crate::contest::report::{fetch_report, ContestReport};
+use crate::contest::series::{fetch_series, Sampling, Series};
+use crate::contest::snapshot::recompute_final_ranking;
+use crate::contest::spectators::{create_spectator_token, delete_spectator_token, reveal_ranking};
 use crate::error::IntoStatusResult;
 use super::{ApiError, ApiInputResult, ApiResponse, ApiUser};
 
+pub mod exports;
 pub mod jollies;
+pub mod questions;
 pub mod teams;
 pub mod submissions;
 
@@ -25,18 +38,70 @@ pub struct ContestsGetResponse {
     contests: Vec<i32>,
 }
 
+/// One or more PhiQuadro sessions of the same contest
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum PhiQuadroSessions {
+    One(u32),
+    Many(Vec<u32>),
+}
+
+impl PhiQuadroSessions {
+    fn to_vec(&self) -> Vec<u32> {
+        match self {
+            PhiQuadroSessions::One(sess) => vec![*sess],
+            PhiQuadroSessions::Many(sessions) => sessions.clone(),
+        }
+    }
+}
+
 #[derive(Deserialize)]
 pub struct ContestPostData<'r> {
-    phiquadro_id: u32,
-    phiquadro_sess: u32,
+    phiquadro_id: Option<u32>,
+    phiquadro_sess: Option<PhiQuadroSessions>,
+    answers: Option<Vec<i32>>,
+    #[serde(default)]
+    ghost_teams: Vec<JsonTeam>,
     name: &'r str,
     start_time: &'r str,
+    #[serde(default)]
+    start_offset: u16,
     duration: u16,
     drift: u32,
     drift_time: u16,
     jolly_time: u16,
     question_bonus: [i32; 10],
     contest_bonus: [i32; 10],
+    time_scale: Option<f64>,
+    team_selection: Option<TeamSelection>,
+    #[serde(default)]
+    count_hidden_for_drift: bool,
+    freeze_time: Option<u16>,
+}
+
+#[derive(Deserialize)]
+pub struct ContestImportData<'r> {
+    name: &'r str,
+    start_time: &'r str,
+    #[serde(default)]
+    start_offset: u16,
+    duration: u16,
+    drift: u32,
+    drift_time: u16,
+    jolly_time: u16,
+    question_bonus: [i32; 10],
+    contest_bonus: [i32; 10],
+    time_scale: Option<f64>,
+    document: ContestDocument,
+    team_selection: Option<TeamSelection>,
+    #[serde(default)]
+    count_hidden_for_drift: bool,
+    freeze_time: Option<u16>,
+}
+
+#[derive(Serialize)]
+pub struct SpectatorPostResponse {
+    token: String,
 }
 
 #[derive(Serialize)]
@@ -46,8 +111,8 @@ pub struct ContestPostResponse {
 
 #[derive(Queryable, Serialize)]
 pub struct ContestGetResponse {
-    phiquadro_id: i32,
-    phiquadro_sess: i32,
+    phiquadro_id: Option<i32>,
+    phiquadro_sess: Option<i32>,
     name: String,
     duration: i32,
     start_time: NaiveDateTime,
@@ -56,6 +121,11 @@ pub struct ContestGetResponse {
     jolly_time: i32,
     question_bonus: Vec<Option<i32>>,
     contest_bonus: Vec<Option<i32>>,
+    time_scale: f64,
+    paused_at: Option<NaiveDateTime>,
+    start_offset: i32,
+    freeze_time: Option<i32>,
+    revealed: bool,
 }
 
 #[derive(Deserialize)]
@@ -67,6 +137,32 @@ pub struct ContestPatchData<'r> {
     jolly_time: Option<u16>,
     question_bonus: Option<[i32; 10]>,
     contest_bonus: Option<[i32; 10]>,
+    time_scale: Option<f64>,
+    start_offset: Option<u16>,
+    /// `Some(None)` removes the freeze, while a missing field leaves it as it is
+    #[serde(default, deserialize_with = "deserialize_some")]
+    freeze_time: Option<Option<u16>>,
+}
+
+/// Tells a field set to `null` from a missing one, which is `None` thanks to `#[serde(default)]`
+fn deserialize_some<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+#[derive(Deserialize)]
+pub struct ContestRestartData<'r> {
+    start_time: &'r str,
+    start_offset: Option<u16>,
+    #[serde(default = "default_keep_teams")]
+    keep_teams: bool,
+}
+
+fn default_keep_teams() -> bool {
+    true
 }
 
 #[derive(AsChangeset)]
@@ -79,6 +175,9 @@ pub struct ContestUpdateForm {
     pub jolly_time: Option<i32>,
     pub question_bonus: Option<Vec<Option<i32>>>,
     pub contest_bonus: Option<Vec<Option<i32>>>,
+    pub time_scale: Option<f64>,
+    pub start_offset: Option<i32>,
+    pub freeze_time: Option<Option<i32>>,
 }
 
 #[get("/contests")]
@@ -107,7 +206,7 @@ pub async fn get_contests<'r>(
 pub async fn post_contest<'r>(
     contest: ApiInputResult<'r, ContestPostData<'r>>,
     mut db: Connection<DB>,
-    phi: &State<PhiQuadroLogin>,
+    config: &State<Config>,
     api_user: ApiUser,
 ) -> Result<ApiResponse<'r, ContestPostResponse>, ApiResponse<'r, ApiError>> {
     let Ok(contest) = contest else {
@@ -134,22 +233,111 @@ pub async fn post_contest<'r>(
         });
     }
 
-    let contest_id = create_contest(
-        &mut db,
-        phi.inner(),
-        api_user.user_id,
-        contest.name,
-        contest.phiquadro_id,
-        contest.phiquadro_sess,
-        contest.duration as u32 * 60,
+    let settings = ContestSettings {
+        owner_id: api_user.user_id,
+        name: contest.name,
+        duration: contest.duration as u32 * 60,
         start_time,
-        contest.drift,
-        contest.drift_time as u32 * 60,
-        contest.jolly_time as u32 * 60,
-        contest.question_bonus,
-        contest.contest_bonus,
+        start_offset: contest.start_offset as u32 * 60,
+        drift: contest.drift,
+        drift_time: contest.drift_time as u32 * 60,
+        jolly_time: contest.jolly_time as u32 * 60,
+        question_bonus: contest.question_bonus,
+        contest_bonus: contest.contest_bonus,
+        time_scale: contest.time_scale.unwrap_or(1.0),
+        team_selection: contest.team_selection.clone(),
+        hidden_drift: contest.count_hidden_for_drift,
+        freeze_time: contest.freeze_time.map(|freeze_time| freeze_time as u32 * 60),
+    };
+
+    let contest = contest.into_inner();
+    let contest_id = match (contest.phiquadro_id, contest.phiquadro_sess, contest.answers) {
+        (Some(phiquadro_id), Some(phiquadro_sess), None) => {
+            let Some(phi) = &config.phiquadro else {
+                return Err(ApiResponse {
+                    status: Status::ServiceUnavailable,
+                    body: ApiError { error: "L'importazione da PhiQuadro non è configurata su questo server".to_string() },
+                    headers: HeaderMap::new(),
+                });
+            };
+
+            create_contest(&mut db, phi, phiquadro_id, &phiquadro_sess.to_vec(), settings).await?
+        }
+        (None, None, Some(answers)) => {
+            let ghost_teams = contest.ghost_teams
+                .into_iter()
+                .map(|team| team.into_team_data())
+                .collect::<anyhow::Result<_>>()
+                .attach_info(Status::UnprocessableEntity, "I quesiti delle squadre fantasma sono numerati a partire da 1")?;
+
+            create_custom_contest(&mut db, answers, ghost_teams, settings).await?
+        }
+        _ => return Err(ApiResponse {
+            status: Status::UnprocessableEntity,
+            body: ApiError { error: "Indica la gara su PhiQuadro oppure le risposte dei quesiti".to_string() },
+            headers: HeaderMap::new(),
+        }),
+    };
+
+    let mut headers = HeaderMap::new();
+    headers.add(Header::new(header::LOCATION.as_str(), format!("/contest/{contest_id}")));
+
+    Ok(ApiResponse {
+        status: Status::Created,
+        body: ContestPostResponse { contest_id },
+        headers,
+    })
+}
+
+#[post("/contests/import", format = "application/json", data = "<contest>")]
+pub async fn post_contest_import<'r>(
+    contest: ApiInputResult<'r, ContestImportData<'r>>,
+    mut db: Connection<DB>,
+    api_user: ApiUser,
+) -> Result<ApiResponse<'r, ContestPostResponse>, ApiResponse<'r, ApiError>> {
+    let Ok(contest) = contest else {
+        return Err(ApiResponse {
+            status: Status::BadRequest,
+            body: ApiError { error: "Richiesta malformata".to_string() },
+            headers: HeaderMap::new(),
+        });
+    };
+
+    let start_time = Rome.from_local_datetime(
+        &NaiveDateTime::parse_from_str(contest.start_time, "%Y-%m-%dT%H:%M")
+            .map_err(|err| anyhow!("Failed to get start datetime: {}", err))
+            .attach_info(Status::BadRequest, "Ora di inizio non valida")?
     )
-    .await?;
+    .unwrap()
+    .with_timezone(&Utc);
+
+    if start_time <= chrono::offset::Utc::now() {
+        return Err(ApiResponse {
+            status: Status::UnprocessableEntity,
+            body: ApiError { error: "La gara non può iniziare nel passato".to_string() },
+            headers: HeaderMap::new(),
+        });
+    }
+
+    let settings = ContestSettings {
+        owner_id: api_user.user_id,
+        name: contest.name,
+        duration: contest.duration as u32 * 60,
+        start_time,
+        start_offset: contest.start_offset as u32 * 60,
+        drift: contest.drift,
+        drift_time: contest.drift_time as u32 * 60,
+        jolly_time: contest.jolly_time as u32 * 60,
+        question_bonus: contest.question_bonus,
+        contest_bonus: contest.contest_bonus,
+        time_scale: contest.time_scale.unwrap_or(1.0),
+        team_selection: contest.team_selection.clone(),
+        hidden_drift: contest.count_hidden_for_drift,
+        freeze_time: contest.freeze_time.map(|freeze_time| freeze_time as u32 * 60),
+    };
+
+    let contest = contest.into_inner();
+    let contest_id = import_contest(&mut db, contest.document, settings).await?;
 
     let mut headers = HeaderMap::new();
     headers.add(Header::new(header::LOCATION.as_str(), format!("/contest/{contest_id}")));
@@ -181,6 +369,11 @@ pub async fn get_contest<'r>(
             contests::jolly_time,
             contests::question_bonus,
             contests::contest_bonus,
+            contests::time_scale,
+            contests::paused_at,
+            contests::start_offset,
+            contests::freeze_time,
+            contests::revealed,
         ))
         .filter(contests::dsl::id.eq(id))
         .filter(contests::active.eq(true))
@@ -189,10 +382,50 @@ pub async fn get_contest<'r>(
         .await
         .attach_info(Status::InternalServerError, "Errore riscontrato durante l'operazione")?;
 
-    match contest.into_iter().next() {
-        Some(contest) => Ok(ApiResponse {
+    let Some(mut contest) = contest.into_iter().next() else {
+        return Err(ApiResponse {
+            status: Status::NotFound,
+            body: ApiError { error: "La gara non esiste o non ti appartiene".to_string() },
+            headers: HeaderMap::new(),
+        });
+    };
+
+    // The start shown to the user is when the user teams join the contest
+    contest.start_time += contest_to_real_time(TimeDelta::seconds(contest.start_offset as i64), contest.time_scale);
+
+    Ok(ApiResponse {
+        status: Status::Ok,
+        body: contest,
+        headers: HeaderMap::new(),
+    })
+}
+
+#[get("/contests/<id>/series?<sampling>")]
+pub async fn get_contest_series<'r>(
+    id: i32,
+    sampling: Option<&str>,
+    mut db: Connection<DB>,
+    api_user: ApiUser,
+) -> Result<ApiResponse<'r, Series>, ApiResponse<'r, ApiError>> {
+    let sampling = match sampling {
+        None | Some("minute") => Sampling::Minute,
+        Some("event") => Sampling::Event,
+        Some(_) => {
+            return Err(ApiResponse {
+                status: Status::UnprocessableEntity,
+                body: ApiError { error: "Campionamento non valido".to_string() },
+                headers: HeaderMap::new(),
+            });
+        }
+    };
+
+    match fetch_series(&mut db, api_user.user_id, id, sampling)
+        .await
+        .attach_info(Status::InternalServerError, "Errore riscontrato durante l'operazione")?
+    {
+        Some(series) => Ok(ApiResponse {
             status: Status::Ok,
-            body: contest,
+            body: series,
             headers: HeaderMap::new(),
         }),
         None => Err(ApiResponse {
@@ -203,6 +436,68 @@ pub async fn get_contest<'r>(
     }
 }
 
+#[get("/contests/<id>/report")]
+pub async fn get_contest_report<'r>(
+    id: i32,
+    mut db: Connection<DB>,
+    api_user: ApiUser,
+) -> Result<ApiResponse<'r, ContestReport>, ApiResponse<'r, ApiError>> {
+    match fetch_report(&mut db, api_user.user_id, id)
+        .await
+        .attach_info(Status::InternalServerError, "Errore riscontrato durante l'operazione")?
+    {
+        Some(report) => Ok(ApiResponse {
+            status: Status::Ok,
+            body: report,
+            headers: HeaderMap::new(),
+        }),
+        None => Err(ApiResponse {
+            status: Status::NotFound,
+            body: ApiError { error: "La gara non esiste o non ti appartiene".to_string() },
+            headers: HeaderMap::new(),
+        }),
+    }
+}
+
+#[get("/contests/<id>/leaderboard")]
+pub async fn get_contest_leaderboard<'r>(
+    id: i32,
+    mut db: Connection<DB>,
+    api_user: ApiUser,
+) -> Result<ApiResponse<'r, Leaderboard>, ApiResponse<'r, ApiError>> {
+    let leaderboard = fetch_leaderboard(&mut db, api_user.user_id, id).await?;
+
+    Ok(ApiResponse {
+        status: Status::Ok,
+        body: leaderboard,
+        headers: HeaderMap::new(),
+    })
+}
+
+#[put("/contests/<id>/leaderboard", format = "application/json", data = "<settings>")]
+pub async fn put_contest_leaderboard<'r>(
+    id: i32,
+    settings: ApiInputResult<'r, LeaderboardSettings>,
+    mut db: Connection<DB>,
+    api_user: ApiUser,
+) -> Result<ApiResponse<'r, ()>, ApiResponse<'r, ApiError>> {
+    let Ok(settings) = settings else {
+        return Err(ApiResponse {
+            status: Status::BadRequest,
+            body: ApiError { error: "Richiesta malformata".to_string() },
+            headers: HeaderMap::new(),
+        });
+    };
+
+    set_leaderboard_settings(&mut db, api_user.user_id, id, &settings).await?;
+
+    Ok(ApiResponse {
+        status: Status::NoContent,
+        body: (),
+        headers: HeaderMap::new(),
+    })
+}
+
 #[patch("/contests/<id>", format = "application/json", data = "<data>")]
 pub async fn patch_contest<'r>(
     id: i32,
@@ -246,22 +541,42 @@ pub async fn patch_contest<'r>(
     )
     .transpose()?;
 
+    let time_scale = data.time_scale.map(|time_scale| {
+        if (MIN_TIME_SCALE..=MAX_TIME_SCALE).contains(&time_scale) {
+            Ok(time_scale)
+        } else {
+            Err(anyhow!("Time scale should be a reasonable value ({} given)", time_scale))
+        }
+        .attach_info(Status::UnprocessableEntity, "Velocità di riproduzione non valida")
+    })
+    .transpose()?;
+
     let duration = data.duration.map(|duration| duration as i32 * 60);
+    let start_offset = data.start_offset.map(|start_offset| start_offset as i32 * 60);
     let drift_time = data.drift_time.map(|drift_time| drift_time as i32 * 60);
     let jolly_time = data.jolly_time.map(|jolly_time| jolly_time as i32 * 60);
+    let freeze_time = data.freeze_time.map(|freeze_time| freeze_time.map(|freeze_time| freeze_time as i32 * 60));
     let question_bonus = data.question_bonus.map(|question_bonus| question_bonus.into_iter().map(Some).collect());
     let contest_bonus = data.contest_bonus.map(|question_bonus| question_bonus.into_iter().map(Some).collect());
 
-    let contest_start_time = contests::dsl::contests
-        .select(contests::start_time)
+    let contest_times = contests::dsl::contests
+        .select((
+            contests::start_time,
+            contests::time_scale,
+            contests::start_offset,
+            contests::duration,
+            contests::freeze_time,
+        ))
         .filter(contests::id.eq(id))
         .filter(contests::active.eq(true))
         .filter(contests::owner_id.eq(api_user.user_id))
-        .load::<DateTime<Utc>>(&mut **db)
+        .load::<(DateTime<Utc>, f64, i32, i32, Option<i32>)>(&mut **db)
         .await
         .attach_info(Status::InternalServerError, "Errore incontrato durante l'aggiornamento delle impostazioni")?;
 
-    let Some(&contest_start_time) = contest_start_time.get(0) else {
+    let Some(&(contest_start_time, contest_time_scale, contest_start_offset, contest_duration, contest_freeze_time)) =
+        contest_times.get(0)
+    else {
         return Err(ApiResponse {
             status: Status::NotFound,
             body: ApiError { error: "La gara non esiste o non ti appartiene".to_string() },
@@ -269,7 +584,10 @@ pub async fn patch_contest<'r>(
         });
     };
 
-    if contest_start_time <= chrono::offset::Utc::now() {
+    let contest_join_time = contest_start_time
+        + contest_to_real_time(TimeDelta::seconds(contest_start_offset as i64), contest_time_scale);
+
+    if contest_join_time <= chrono::offset::Utc::now() {
         return Err(ApiResponse {
             status: Status::Forbidden,
             body: ApiError { error: "La gara è già iniziata".to_string() },
@@ -277,6 +595,33 @@ pub async fn patch_contest<'r>(
         });
     }
 
+    if start_offset.unwrap_or(contest_start_offset) >= duration.unwrap_or(contest_duration) {
+        return Err(ApiResponse {
+            status: Status::UnprocessableEntity,
+            body: ApiError { error: "Il minuto di partenza deve precedere la fine della gara".to_string() },
+            headers: HeaderMap::new(),
+        });
+    }
+
+    if freeze_time
+        .unwrap_or(contest_freeze_time)
+        .is_some_and(|freeze_time| freeze_time >= duration.unwrap_or(contest_duration))
+    {
+        return Err(ApiResponse {
+            status: Status::UnprocessableEntity,
+            body: ApiError { error: "Il congelamento della classifica deve precedere la fine della gara".to_string() },
+            headers: HeaderMap::new(),
+        });
+    }
+
+    // The replay starts earlier than the time chosen by the user, when the user teams join the contest
+    let start_time = (start_time.is_some() || time_scale.is_some() || start_offset.is_some()).then(|| {
+        start_time.unwrap_or(contest_join_time) - contest_to_real_time(
+            TimeDelta::seconds(start_offset.unwrap_or(contest_start_offset) as i64),
+            time_scale.unwrap_or(contest_time_scale),
+        )
+    });
+
     update(
         contests::dsl::contests
             .filter(contests::id.eq(id))
@@ -290,21 +635,30 @@ pub async fn patch_contest<'r>(
             jolly_time,
             question_bonus,
             contest_bonus,
+            time_scale,
+            start_offset,
+            freeze_time,
         })
         .execute(&mut **db)
         .await
         .attach_info(Status::InternalServerError, "Errore incontrato durante l'aggiornamento delle impostazioni")?;
 
-    if let Some(start_time) = start_time {
-        let delta = timedelta_to_pg_interval(start_time - contest_start_time);
+    if let Some(new_start_time) = start_time {
+        // Moving the replayed activity to the new start and stretching it to the new speed
+        let factor = contest_time_scale / time_scale.unwrap_or(contest_time_scale);
+
         sql_query(include_str!("update_times_submissions.sql"))
-            .bind::<Interval, _>(delta)
+            .bind::<Timestamptz, _>(new_start_time)
+            .bind::<Timestamptz, _>(contest_start_time)
+            .bind::<Double, _>(factor)
             .bind::<Integer, _>(id)
             .execute(&mut **db)
             .await
             .attach_info(Status::InternalServerError, "Errore incontrato durante l'aggiornamento delle impostazioni")?;
         sql_query(include_str!("update_times_jollies.sql"))
-            .bind::<Interval, _>(delta)
+            .bind::<Timestamptz, _>(new_start_time)
+            .bind::<Timestamptz, _>(contest_start_time)
+            .bind::<Double, _>(factor)
             .bind::<Integer, _>(id)
             .execute(&mut **db)
             .await
@@ -318,6 +672,349 @@ pub async fn patch_contest<'r>(
     })
 }
 
+#[post("/contests/<id>/spectator")]
+pub async fn post_contest_spectator<'r>(
+    id: i32,
+    mut db: Connection<DB>,
+    api_user: ApiUser,
+) -> Result<ApiResponse<'r, SpectatorPostResponse>, ApiResponse<'r, ApiError>> {
+    let token = create_spectator_token(&mut db, api_user.user_id, id).await?;
+
+    Ok(ApiResponse {
+        status: Status::Created,
+        body: SpectatorPostResponse { token },
+        headers: HeaderMap::new(),
+    })
+}
+
+#[delete("/contests/<id>/spectator")]
+pub async fn delete_contest_spectator<'r>(
+    id: i32,
+    mut db: Connection<DB>,
+    api_user: ApiUser,
+) -> Result<ApiResponse<'r, ()>, ApiResponse<'r, ApiError>> {
+    delete_spectator_token(&mut db, api_user.user_id, id).await?;
+
+    Ok(ApiResponse {
+        status: Status::NoContent,
+        body: (),
+        headers: HeaderMap::new(),
+    })
+}
+
+#[post("/contests/<id>/reveal")]
+pub async fn post_contest_reveal<'r>(
+    id: i32,
+    mut db: Connection<DB>,
+    api_user: ApiUser,
+) -> Result<ApiResponse<'r, ()>, ApiResponse<'r, ApiError>> {
+    reveal_ranking(&mut db, api_user.user_id, id).await?;
+
+    Ok(ApiResponse {
+        status: Status::NoContent,
+        body: (),
+        headers: HeaderMap::new(),
+    })
+}
+
+#[post("/contests/<id>/final_ranking")]
+pub async fn post_contest_final_ranking<'r>(
+    id: i32,
+    mut db: Connection<DB>,
+    api_user: ApiUser,
+) -> Result<ApiResponse<'r, ()>, ApiResponse<'r, ApiError>> {
+    recompute_final_ranking(&mut db, api_user.user_id, id).await?;
+
+    Ok(ApiResponse {
+        status: Status::NoContent,
+        body: (),
+        headers: HeaderMap::new(),
+    })
+}
+
+#[post("/contests/<id>/pause")]
+pub async fn post_contest_pause<'r>(
+    id: i32,
+    mut db: Connection<DB>,
+    api_user: ApiUser,
+) -> Result<ApiResponse<'r, ()>, ApiResponse<'r, ApiError>> {
+    use crate::schema::contests;
+
+    let contest = contests::dsl::contests
+        .select((
+            contests::start_time,
+            contests::duration,
+            contests::time_scale,
+            contests::paused_at,
+            contests::start_offset,
+        ))
+        .filter(contests::id.eq(id))
+        .filter(contests::active.eq(true))
+        .filter(contests::owner_id.eq(api_user.user_id))
+        .load::<(DateTime<Utc>, i32, f64, Option<DateTime<Utc>>, i32)>(&mut **db)
+        .await
+        .attach_info(Status::InternalServerError, "Errore incontrato durante la sospensione della gara")?;
+
+    let Some(&(start_time, duration, time_scale, paused_at, start_offset)) = contest.get(0) else {
+        return Err(ApiResponse {
+            status: Status::NotFound,
+            body: ApiError { error: "La gara non esiste o non ti appartiene".to_string() },
+            headers: HeaderMap::new(),
+        });
+    };
+
+    let now = chrono::offset::Utc::now();
+    let error = if paused_at.is_some() {
+        Some((Status::Conflict, "La gara è già in pausa"))
+    } else if now < start_time + contest_to_real_time(TimeDelta::seconds(start_offset as i64), time_scale) {
+        Some((Status::Forbidden, "La gara non è ancora iniziata"))
+    } else if now > start_time + contest_to_real_time(TimeDelta::seconds(duration as i64), time_scale) {
+        Some((Status::Forbidden, "La gara è terminata"))
+    } else {
+        None
+    };
+
+    if let Some((status, error)) = error {
+        return Err(ApiResponse {
+            status,
+            body: ApiError { error: error.to_string() },
+            headers: HeaderMap::new(),
+        });
+    }
+
+    update(contests::dsl::contests.filter(contests::id.eq(id)))
+        .set(contests::paused_at.eq(now))
+        .execute(&mut **db)
+        .await
+        .attach_info(Status::InternalServerError, "Errore incontrato durante la sospensione della gara")?;
+
+    Ok(ApiResponse {
+        status: Status::NoContent,
+        body: (),
+        headers: HeaderMap::new(),
+    })
+}
+
+#[post("/contests/<id>/resume")]
+pub async fn post_contest_resume<'r>(
+    id: i32,
+    mut db: Connection<DB>,
+    api_user: ApiUser,
+) -> Result<ApiResponse<'r, ()>, ApiResponse<'r, ApiError>> {
+    use crate::schema::contests;
+
+    let contest = contests::dsl::contests
+        .select((contests::start_time, contests::paused_at))
+        .filter(contests::id.eq(id))
+        .filter(contests::active.eq(true))
+        .filter(contests::owner_id.eq(api_user.user_id))
+        .load::<(DateTime<Utc>, Option<DateTime<Utc>>)>(&mut **db)
+        .await
+        .attach_info(Status::InternalServerError, "Errore incontrato durante la ripresa della gara")?;
+
+    let Some(&(start_time, paused_at)) = contest.get(0) else {
+        return Err(ApiResponse {
+            status: Status::NotFound,
+            body: ApiError { error: "La gara non esiste o non ti appartiene".to_string() },
+            headers: HeaderMap::new(),
+        });
+    };
+
+    let Some(paused_at) = paused_at else {
+        return Err(ApiResponse {
+            status: Status::Conflict,
+            body: ApiError { error: "La gara non è in pausa".to_string() },
+            headers: HeaderMap::new(),
+        });
+    };
+
+    // The whole contest is moved forward by the length of the pause, so that the contest time elapsed before the
+    // pause is preserved and the activity still to be replayed is delayed
+    let new_start_time = start_time + (chrono::offset::Utc::now() - paused_at);
+
+    db.transaction::<_, anyhow::Error, _>(|conn| async move {
+        sql_query(include_str!("update_times_submissions.sql"))
+            .bind::<Timestamptz, _>(new_start_time)
+            .bind::<Timestamptz, _>(start_time)
+            .bind::<Double, _>(1.0)
+            .bind::<Integer, _>(id)
+            .execute(conn)
+            .await?;
+        sql_query(include_str!("update_times_jollies.sql"))
+            .bind::<Timestamptz, _>(new_start_time)
+            .bind::<Timestamptz, _>(start_time)
+            .bind::<Double, _>(1.0)
+            .bind::<Integer, _>(id)
+            .execute(conn)
+            .await?;
+
+        update(contests::dsl::contests.filter(contests::id.eq(id)))
+            .set((contests::start_time.eq(new_start_time), contests::paused_at.eq(None::<DateTime<Utc>>)))
+            .execute(conn)
+            .await?;
+
+        Ok(())
+    }.scope_boxed())
+    .await
+    .attach_info(Status::InternalServerError, "Errore incontrato durante la ripresa della gara")?;
+
+    Ok(ApiResponse {
+        status: Status::NoContent,
+        body: (),
+        headers: HeaderMap::new(),
+    })
+}
+
+#[post("/contests/<id>/restart", format = "application/json", data = "<data>")]
+pub async fn post_contest_restart<'r>(
+    id: i32,
+    data: ApiInputResult<'r, ContestRestartData<'r>>,
+    mut db: Connection<DB>,
+    api_user: ApiUser,
+) -> Result<ApiResponse<'r, ()>, ApiResponse<'r, ApiError>> {
+    use crate::schema::{contests, final_rankings, jollies, submissions, teams};
+
+    let Ok(data) = data else {
+        return Err(ApiResponse {
+            status: Status::BadRequest,
+            body: ApiError { error: "Richiesta malformata".to_string() },
+            headers: HeaderMap::new(),
+        });
+    };
+
+    let start_time = Rome.from_local_datetime(
+        &NaiveDateTime::parse_from_str(data.start_time, "%Y-%m-%dT%H:%M")
+            .map_err(|err| anyhow!("Failed to get start datetime: {}", err))
+            .attach_info(Status::BadRequest, "Ora di inizio non valida")?
+    )
+    .unwrap()
+    .with_timezone(&Utc);
+
+    if start_time <= chrono::offset::Utc::now() {
+        return Err(ApiResponse {
+            status: Status::UnprocessableEntity,
+            body: ApiError { error: "La gara non può iniziare nel passato".to_string() },
+            headers: HeaderMap::new(),
+        });
+    }
+
+    let contest_times = contests::dsl::contests
+        .select((contests::start_time, contests::time_scale, contests::start_offset, contests::duration))
+        .filter(contests::id.eq(id))
+        .filter(contests::active.eq(true))
+        .filter(contests::owner_id.eq(api_user.user_id))
+        .load::<(DateTime<Utc>, f64, i32, i32)>(&mut **db)
+        .await
+        .attach_info(Status::InternalServerError, "Errore incontrato durante il riavvio della gara")?;
+
+    let Some(&(contest_start_time, time_scale, contest_start_offset, duration)) = contest_times.get(0) else {
+        return Err(ApiResponse {
+            status: Status::NotFound,
+            body: ApiError { error: "La gara non esiste o non ti appartiene".to_string() },
+            headers: HeaderMap::new(),
+        });
+    };
+
+    let start_offset = data.start_offset.map_or(contest_start_offset, |start_offset| start_offset as i32 * 60);
+    if start_offset >= duration {
+        return Err(ApiResponse {
+            status: Status::UnprocessableEntity,
+            body: ApiError { error: "Il minuto di partenza deve precedere la fine della gara".to_string() },
+            headers: HeaderMap::new(),
+        });
+    }
+
+    // The replay starts earlier than the time chosen by the user, when the user teams join the contest
+    let start_time = start_time - contest_to_real_time(TimeDelta::seconds(start_offset as i64), time_scale);
+
+    db.transaction::<_, anyhow::Error, _>(|conn| async move {
+        // Removing everything the users did in the contest
+        let user_teams = teams::dsl::teams
+            .select(teams::id)
+            .filter(teams::contest_id.eq(id))
+            .filter(teams::is_fake.eq(false));
+
+        diesel::delete(submissions::dsl::submissions.filter(submissions::team_id.eq_any(user_teams)))
+            .execute(conn)
+            .await?;
+
+        diesel::delete(jollies::dsl::jollies.filter(jollies::team_id.eq_any(user_teams)))
+            .execute(conn)
+            .await?;
+
+        if !data.keep_teams {
+            diesel::delete(
+                teams::dsl::teams
+                    .filter(teams::contest_id.eq(id))
+                    .filter(teams::is_fake.eq(false))
+            )
+                .execute(conn)
+                .await?;
+
+            // The remaining teams are moved down to fill the holes left by the user teams, going in order of
+            // position so that no two teams ever share one
+            let remaining = teams::dsl::teams
+                .select((teams::id, teams::is_fake))
+                .filter(teams::contest_id.eq(id))
+                .order(teams::position.asc())
+                .load::<(i32, bool)>(conn)
+                .await?;
+
+            for (position, &(team_id, _)) in remaining.iter().enumerate() {
+                update(teams::dsl::teams.filter(teams::id.eq(team_id)))
+                    .set(teams::position.eq(position as i32))
+                    .execute(conn)
+                    .await?;
+            }
+
+            update(contests::dsl::contests.filter(contests::id.eq(id)))
+                .set(contests::teams_no.eq(remaining.iter().filter(|&&(_, is_fake)| is_fake).count() as i32))
+                .execute(conn)
+                .await?;
+        }
+
+        // Moving the replayed activity to the new start
+        sql_query(include_str!("update_times_submissions.sql"))
+            .bind::<Timestamptz, _>(start_time)
+            .bind::<Timestamptz, _>(contest_start_time)
+            .bind::<Double, _>(1.0)
+            .bind::<Integer, _>(id)
+            .execute(conn)
+            .await?;
+        sql_query(include_str!("update_times_jollies.sql"))
+            .bind::<Timestamptz, _>(start_time)
+            .bind::<Timestamptz, _>(contest_start_time)
+            .bind::<Double, _>(1.0)
+            .bind::<Integer, _>(id)
+            .execute(conn)
+            .await?;
+
+        update(contests::dsl::contests.filter(contests::id.eq(id)))
+            .set((
+                contests::start_time.eq(start_time),
+                contests::start_offset.eq(start_offset),
+                contests::paused_at.eq(None::<DateTime<Utc>>),
+                contests::revealed.eq(false),
+            ))
+            .execute(conn)
+            .await?;
+
+        diesel::delete(final_rankings::dsl::final_rankings.filter(final_rankings::contest_id.eq(id)))
+            .execute(conn)
+            .await?;
+
+        Ok(())
+    }.scope_boxed())
+    .await
+    .attach_info(Status::InternalServerError, "Errore incontrato durante il riavvio della gara")?;
+
+    Ok(ApiResponse {
+        status: Status::NoContent,
+        body: (),
+        headers: HeaderMap::new(),
+    })
+}
+
 #[delete("/contests/<id>")]
 pub async fn delete_contest<'r>(
     id: i32,
@@ -0,0 +1,40 @@
+use rocket::http::{HeaderMap, Status};
+use rocket_db_pools::Connection;
+
+use crate::api::{ApiError, ApiResponse, ApiUser};
+use crate::contest::stats::{fetch_questions_stats, QuestionStats};
+use crate::error::IntoStatusResult;
+use crate::DB;
+
+#[get("/contests/<id>/questions/<pos>/stats")]
+pub async fn get_question_stats<'r>(
+    id: i32,
+    pos: usize,
+    mut db: Connection<DB>,
+    api_user: ApiUser,
+) -> Result<ApiResponse<'r, QuestionStats>, ApiResponse<'r, ApiError>> {
+    let Some(stats) = fetch_questions_stats(&mut db, api_user.user_id, id)
+        .await
+        .attach_info(Status::InternalServerError, "Errore riscontrato durante l'operazione")?
+    else {
+        return Err(ApiResponse {
+            status: Status::NotFound,
+            body: ApiError { error: "La gara non esiste o non ti appartiene".to_string() },
+            headers: HeaderMap::new(),
+        });
+    };
+
+    // Questions are numbered from 1, as in the ranking
+    match pos.checked_sub(1).and_then(|pos| stats.into_iter().nth(pos)) {
+        Some(question) => Ok(ApiResponse {
+            status: Status::Ok,
+            body: question,
+            headers: HeaderMap::new(),
+        }),
+        None => Err(ApiResponse {
+            status: Status::NotFound,
+            body: ApiError { error: "Il quesito non esiste".to_string() },
+            headers: HeaderMap::new(),
+        }),
+    }
+}
@@ -1,16 +1,20 @@
+use std::collections::{HashMap, HashSet};
+
 use chrono::{DateTime, Duration, Utc};
 use diesel::prelude::Queryable;
 use diesel::{ExpressionMethods, QueryDsl};
 use rocket::http::hyper::header;
 use rocket::http::{Header, HeaderMap, Status};
 use rocket::serde::json::Json;
-use rocket_db_pools::diesel::prelude::RunQueryDsl;
+use rocket_db_pools::diesel::prelude::{AsyncConnection, RunQueryDsl};
+use rocket_db_pools::diesel::scoped_futures::ScopedFutureExt;
 use rocket_db_pools::Connection;
 use serde::{Deserialize, Serialize};
 
-use crate::api::{ApiError, ApiInputResult, ApiResponse, ApiUser};
+use crate::api::{prop_error, ApiError, ApiInputResult, ApiResponse, ApiUser, IdempotencyKey};
+use crate::contest::snapshot::delete_final_ranking;
 use crate::error::IntoStatusResult;
-use crate::model::Submission;
+use crate::model::{contest_to_real_time, Submission};
 use crate::DB;
 
 #[derive(Serialize)]
@@ -26,17 +30,32 @@ pub struct SubmissionGetResponse {
     question_id: i32,
 }
 
+/// How long after being given an answer can still be entered with its real time, in real minutes
+const MAX_SUBMISSION_LAG_MINUTES: i64 = 5;
+/// How many submissions can be sent in a single batch
+const MAX_BATCH_SIZE: usize = 1000;
+
 #[derive(Deserialize)]
 pub struct SubmissionPostData {
     answer: i32,
     team_id: i32,
     question_id: i32,
+    /// When the answer was given, if it is entered later, as it happens when the answers are collected on paper
+    sub_time: Option<DateTime<Utc>>,
 }
 
 #[derive(Serialize)]
 pub struct SubmissionPostResponse {
     submission_id: i32,
     correct: bool,
+    /// Whether the team had already given the same answer to the question
+    duplicate: bool,
+}
+
+#[derive(Serialize)]
+pub struct SubmissionsBatchPostResponse {
+    /// In the same order as the submissions sent
+    submissions: Vec<SubmissionPostResponse>,
 }
 
 #[derive(Deserialize)]
@@ -86,32 +105,40 @@ pub async fn get_submissions<'r>(
     })
 }
 
-#[post("/contests/<id>/submissions", format = "application/json", data = "<submission>")]
-pub async fn post_submission<'r>(
-    id: i32,
-    submission: ApiInputResult<'_, SubmissionPostData>,
-    mut db: Connection<DB>,
-    api_user: ApiUser,
-) -> Result<ApiResponse<'r, SubmissionPostResponse>, ApiResponse<'r, ApiError>> {
-    use crate::schema::{contests, teams, questions, submissions};
+/// What the submissions to a contest are checked against
+struct SubmissionRules {
+    join: DateTime<Utc>,
+    end: DateTime<Utc>,
+    paused_at: Option<DateTime<Utc>>,
+    /// The teams of the user, the only ones which can submit
+    teams: HashSet<i32>,
+    /// The correct answer of each question
+    answers: HashMap<i32, i32>,
+}
 
-    let Ok(submission) = submission else {
-        return Err(ApiResponse {
-            status: Status::BadRequest,
-            body: ApiError { error: "Richiesta malformata".to_string() },
-            headers: HeaderMap::new(),
-        });
-    };
+async fn fetch_submission_rules<'r>(
+    db: &mut Connection<DB>,
+    user_id: i32,
+    id: i32,
+) -> Result<SubmissionRules, ApiResponse<'r, ApiError>> {
+    use crate::schema::{contests, teams, questions};
 
     let contest = contests::dsl::contests
-        .select((contests::owner_id, contests::start_time, contests::duration))
+        .select((
+            contests::owner_id,
+            contests::start_time,
+            contests::duration,
+            contests::time_scale,
+            contests::paused_at,
+            contests::start_offset,
+        ))
         .filter(contests::id.eq(id))
         .filter(contests::active.eq(true))
-        .load::<(i32, DateTime<Utc>, i32)>(&mut **db)
+        .load::<(i32, DateTime<Utc>, i32, f64, Option<DateTime<Utc>>, i32)>(db)
         .await
         .attach_info(Status::InternalServerError, "Errore incontrato durante l'invio della risposta")?;
 
-    let Some(&(contest_owner, start, duration)) = contest.get(0) else {
+    let Some(&(contest_owner, start, duration, time_scale, paused_at, start_offset)) = contest.get(0) else {
         return Err(ApiResponse {
             status: Status::NotFound,
             body: ApiError { error: "La gara non esiste o non ti appartiene".to_string() },
@@ -119,7 +146,7 @@ pub async fn post_submission<'r>(
         });
     };
 
-    if contest_owner != api_user.user_id {
+    if contest_owner != user_id {
         return Err(ApiResponse {
             status: Status::NotFound,
             body: ApiError { error: "La gara non esiste o non ti appartiene".to_string() },
@@ -127,24 +154,38 @@ pub async fn post_submission<'r>(
         });
     }
 
-    let team = teams::dsl::teams
-        .count()
-        .filter(teams::id.eq(submission.team_id))
+    let teams = teams::dsl::teams
+        .select(teams::id)
         .filter(teams::is_fake.eq(false))
         .filter(teams::contest_id.eq(id))
-        .load::<i64>(&mut **db)
+        .load::<i32>(db)
         .await
         .attach_info(Status::InternalServerError, "Errore incontrato durante l'invio della risposta")?;
 
-    let question = questions::dsl::questions
-        .select(questions::answer)
-        .filter(questions::id.eq(submission.question_id))
+    let answers = questions::dsl::questions
+        .select((questions::id, questions::answer))
         .filter(questions::contest_id.eq(id))
-        .load::<i32>(&mut **db)
+        .load::<(i32, i32)>(db)
         .await
         .attach_info(Status::InternalServerError, "Errore incontrato durante l'invio della risposta")?;
 
-    if team[0] == 0 {
+    Ok(SubmissionRules {
+        // User teams can only play after joining the contest
+        join: start + contest_to_real_time(Duration::seconds(start_offset as i64), time_scale),
+        end: start + contest_to_real_time(Duration::seconds(duration as i64), time_scale),
+        paused_at,
+        teams: teams.into_iter().collect(),
+        answers: answers.into_iter().collect(),
+    })
+}
+
+/// Checks whether a submission can be accepted at `curr_time`, returning when it was given and whether it is correct
+fn check_submission<'r>(
+    rules: &SubmissionRules,
+    submission: &SubmissionPostData,
+    curr_time: DateTime<Utc>,
+) -> Result<(DateTime<Utc>, bool), ApiResponse<'r, ApiError>> {
+    if !rules.teams.contains(&submission.team_id) {
         return Err(ApiResponse {
             status: Status::NotFound,
             body: ApiError { error: "La squadra non esiste o non ti appartiene".to_string() },
@@ -152,7 +193,7 @@ pub async fn post_submission<'r>(
         });
     }
 
-    let Some(&answer) = question.get(0) else {
+    let Some(&answer) = rules.answers.get(&submission.question_id) else {
         return Err(ApiResponse {
             status: Status::NotFound,
             body: ApiError { error: "La domanda non esiste o non ti appartiene".to_string() },
@@ -160,8 +201,7 @@ pub async fn post_submission<'r>(
         });
     };
 
-    let curr_time = chrono::Utc::now();
-    if curr_time < start {
+    if curr_time < rules.join {
         return Err(ApiResponse {
             status: Status::Forbidden,
             body: ApiError { error: "La gara non è ancora iniziata".to_string() },
@@ -169,33 +209,309 @@ pub async fn post_submission<'r>(
         });
     }
 
-    if curr_time > start + Duration::seconds(duration as i64) {
+    let sub_time = match submission.sub_time {
+        Some(sub_time) => {
+            // While the contest is paused its clock is stopped
+            let now = rules.paused_at.unwrap_or(curr_time);
+
+            if sub_time > now {
+                return Err(ApiResponse {
+                    status: Status::UnprocessableEntity,
+                    body: ApiError { error: "L'orario della risposta non può essere nel futuro".to_string() },
+                    headers: HeaderMap::new(),
+                });
+            }
+
+            if now - sub_time > Duration::minutes(MAX_SUBMISSION_LAG_MINUTES) {
+                return Err(ApiResponse {
+                    status: Status::UnprocessableEntity,
+                    body: ApiError {
+                        error: format!(
+                            "Le risposte si possono inserire al massimo {} minuti dopo la consegna",
+                            MAX_SUBMISSION_LAG_MINUTES,
+                        ),
+                    },
+                    headers: HeaderMap::new(),
+                });
+            }
+
+            if sub_time < rules.join || sub_time > rules.end {
+                return Err(ApiResponse {
+                    status: Status::UnprocessableEntity,
+                    body: ApiError { error: "L'orario della risposta non è durante la gara".to_string() },
+                    headers: HeaderMap::new(),
+                });
+            }
+
+            sub_time
+        }
+        None => {
+            if rules.paused_at.is_some() {
+                return Err(ApiResponse {
+                    status: Status::Forbidden,
+                    body: ApiError { error: "La gara è in pausa".to_string() },
+                    headers: HeaderMap::new(),
+                });
+            }
+
+            if curr_time > rules.end {
+                return Err(ApiResponse {
+                    status: Status::Forbidden,
+                    body: ApiError { error: "Il tempo per la consegna delle risposte è scaduto".to_string() },
+                    headers: HeaderMap::new(),
+                });
+            }
+
+            curr_time
+        }
+    };
+
+    Ok((sub_time, answer == submission.answer))
+}
+
+/// Looks for the submission a team sent with an idempotency key, returning its id, question and answer
+async fn fetch_keyed_submission<'r>(
+    db: &mut Connection<DB>,
+    team_id: i32,
+    key: &str,
+) -> Result<Option<(i32, i32, i32)>, ApiResponse<'r, ApiError>> {
+    use crate::schema::submissions;
+
+    let submission = submissions::dsl::submissions
+        .select((submissions::id, submissions::question_id, submissions::answer))
+        .filter(submissions::team_id.eq(team_id))
+        .filter(submissions::idempotency_key.eq(key))
+        .load::<(i32, i32, i32)>(db)
+        .await
+        .attach_info(Status::InternalServerError, "Errore incontrato durante l'invio della risposta")?;
+
+    Ok(submission.into_iter().next())
+}
+
+/// Answers a retried request with what was answered when the submission was first inserted
+async fn replay_submission<'r>(
+    db: &mut Connection<DB>,
+    id: i32,
+    rules: &SubmissionRules,
+    submission: &SubmissionPostData,
+    (submission_id, question_id, answer): (i32, i32, i32),
+) -> Result<ApiResponse<'r, SubmissionPostResponse>, ApiResponse<'r, ApiError>> {
+    use crate::schema::submissions;
+
+    if question_id != submission.question_id || answer != submission.answer {
         return Err(ApiResponse {
-            status: Status::Forbidden,
-            body: ApiError { error: "Il tempo per la consegna delle risposte è scaduto".to_string() },
+            status: Status::UnprocessableEntity,
+            body: ApiError { error: "La chiave di idempotenza è già stata usata per un'altra risposta".to_string() },
             headers: HeaderMap::new(),
         });
     }
 
-    let submission_id = diesel::insert_into(submissions::dsl::submissions)
-        .values(Submission {
-            answer: submission.answer,
-            sub_time: chrono::Utc::now(),
-            team_id: submission.team_id,
-            question_id: submission.question_id,
-        })
-        .returning(submissions::id)
-        .get_result(&mut **db)
+    let duplicates: i64 = submissions::dsl::submissions
+        .filter(submissions::team_id.eq(submission.team_id))
+        .filter(submissions::question_id.eq(question_id))
+        .filter(submissions::answer.eq(answer))
+        .filter(submissions::id.lt(submission_id))
+        .count()
+        .get_result(db)
         .await
         .attach_info(Status::InternalServerError, "Errore incontrato durante l'invio della risposta")?;
 
+    Ok(created_submission(id, SubmissionPostResponse {
+        submission_id,
+        correct: rules.answers.get(&question_id) == Some(&answer),
+        duplicate: duplicates > 0,
+    }))
+}
+
+fn created_submission<'r>(id: i32, body: SubmissionPostResponse) -> ApiResponse<'r, SubmissionPostResponse> {
     let mut headers = HeaderMap::new();
-    headers.add(Header::new(header::LOCATION.as_str(), format!("/contest/{id}/submissions/{submission_id}")));
+    headers.add(Header::new(
+        header::LOCATION.as_str(),
+        format!("/contest/{id}/submissions/{}", body.submission_id),
+    ));
 
-    Ok(ApiResponse {
+    ApiResponse {
         status: Status::Created,
-        body: SubmissionPostResponse { submission_id, correct: answer == submission.answer },
+        body,
         headers,
+    }
+}
+
+#[post("/contests/<id>/submissions", format = "application/json", data = "<submission>")]
+pub async fn post_submission<'r>(
+    id: i32,
+    submission: ApiInputResult<'_, SubmissionPostData>,
+    key: IdempotencyKey,
+    mut db: Connection<DB>,
+    api_user: ApiUser,
+) -> Result<ApiResponse<'r, SubmissionPostResponse>, ApiResponse<'r, ApiError>> {
+    use crate::schema::submissions;
+
+    let Ok(submission) = submission else {
+        return Err(ApiResponse {
+            status: Status::BadRequest,
+            body: ApiError { error: "Richiesta malformata".to_string() },
+            headers: HeaderMap::new(),
+        });
+    };
+    key.validate()?;
+
+    let rules = fetch_submission_rules(&mut db, api_user.user_id, id).await?;
+
+    // A retried request is answered as the first time, even if meanwhile the contest has ended
+    if let Some(key) = &key.0 {
+        if !rules.teams.contains(&submission.team_id) {
+            return Err(ApiResponse {
+                status: Status::NotFound,
+                body: ApiError { error: "La squadra non esiste o non ti appartiene".to_string() },
+                headers: HeaderMap::new(),
+            });
+        }
+
+        if let Some(sent) = fetch_keyed_submission(&mut db, submission.team_id, key).await? {
+            return replay_submission(&mut db, id, &rules, &submission, sent).await;
+        }
+    }
+
+    let curr_time = chrono::Utc::now();
+    let (sub_time, correct) = check_submission(&rules, &submission, curr_time)?;
+
+    let duplicates: i64 = submissions::dsl::submissions
+        .filter(submissions::team_id.eq(submission.team_id))
+        .filter(submissions::question_id.eq(submission.question_id))
+        .filter(submissions::answer.eq(submission.answer))
+        .count()
+        .get_result(&mut **db)
+        .await
+        .attach_info(Status::InternalServerError, "Errore incontrato durante l'invio della risposta")?;
+
+    let inserted = diesel::insert_into(submissions::dsl::submissions)
+        .values((
+            Submission {
+                answer: submission.answer,
+                sub_time,
+                team_id: submission.team_id,
+                question_id: submission.question_id,
+            },
+            submissions::idempotency_key.eq(&key.0),
+        ))
+        .returning(submissions::id)
+        .get_result(&mut **db)
+        .await;
+
+    let submission_id = match inserted {
+        Ok(submission_id) => submission_id,
+        Err(err) => {
+            // The same request may have been retried while this one was running
+            if let Some(key) = &key.0 {
+                if let Some(sent) = fetch_keyed_submission(&mut db, submission.team_id, key).await? {
+                    return replay_submission(&mut db, id, &rules, &submission, sent).await;
+                }
+            }
+
+            return Err(prop_error(
+                err,
+                Status::InternalServerError,
+                "Errore incontrato durante l'invio della risposta",
+            ));
+        }
+    };
+
+    // An answer entered after the end changes the final ranking, which is computed again when it is next shown
+    if curr_time > rules.end {
+        delete_final_ranking(&mut db, id)
+            .await
+            .attach_info(Status::InternalServerError, "Errore incontrato durante l'invio della risposta")?;
+    }
+
+    Ok(created_submission(id, SubmissionPostResponse { submission_id, correct, duplicate: duplicates > 0 }))
+}
+
+/// Inserts many submissions at once, all of them or none if any is not valid
+#[post("/contests/<id>/submissions/batch", format = "application/json", data = "<batch>")]
+pub async fn post_submissions_batch<'r>(
+    id: i32,
+    batch: ApiInputResult<'_, Vec<SubmissionPostData>>,
+    mut db: Connection<DB>,
+    api_user: ApiUser,
+) -> Result<ApiResponse<'r, SubmissionsBatchPostResponse>, ApiResponse<'r, ApiError>> {
+    use crate::schema::submissions;
+
+    let Ok(batch) = batch else {
+        return Err(ApiResponse {
+            status: Status::BadRequest,
+            body: ApiError { error: "Richiesta malformata".to_string() },
+            headers: HeaderMap::new(),
+        });
+    };
+
+    if batch.is_empty() || batch.len() > MAX_BATCH_SIZE {
+        return Err(ApiResponse {
+            status: Status::UnprocessableEntity,
+            body: ApiError { error: format!("Invia da 1 a {} risposte alla volta", MAX_BATCH_SIZE) },
+            headers: HeaderMap::new(),
+        });
+    }
+
+    let rules = fetch_submission_rules(&mut db, api_user.user_id, id).await?;
+    let curr_time = chrono::Utc::now();
+
+    let mut checked = vec![];
+    for (i, submission) in batch.iter().enumerate() {
+        match check_submission(&rules, submission, curr_time) {
+            Ok(result) => checked.push(result),
+            Err(mut response) => {
+                response.body.error = format!("Risposta {}: {}", i + 1, response.body.error);
+                return Err(response);
+            }
+        }
+    }
+
+    let batch = &batch;
+    let results = db.transaction::<_, anyhow::Error, _>(|conn| async move {
+        let team_ids: Vec<i32> = batch.iter().map(|submission| submission.team_id).collect();
+        let mut given: HashSet<(i32, i32, i32)> = submissions::dsl::submissions
+            .select((submissions::team_id, submissions::question_id, submissions::answer))
+            .filter(submissions::team_id.eq_any(team_ids))
+            .load::<(i32, i32, i32)>(conn)
+            .await?
+            .into_iter()
+            .collect();
+
+        let mut results = vec![];
+        for (submission, (sub_time, correct)) in batch.iter().zip(checked) {
+            // Also the same answer given twice in the batch is a duplicate
+            let duplicate = !given.insert((submission.team_id, submission.question_id, submission.answer));
+
+            let submission_id = diesel::insert_into(submissions::dsl::submissions)
+                .values(Submission {
+                    answer: submission.answer,
+                    sub_time,
+                    team_id: submission.team_id,
+                    question_id: submission.question_id,
+                })
+                .returning(submissions::id)
+                .get_result(conn)
+                .await?;
+
+            results.push(SubmissionPostResponse { submission_id, correct, duplicate });
+        }
+
+        Ok(results)
+    }.scope_boxed())
+    .await
+    .attach_info(Status::InternalServerError, "Errore incontrato durante l'invio delle risposte")?;
+
+    if curr_time > rules.end {
+        delete_final_ranking(&mut db, id)
+            .await
+            .attach_info(Status::InternalServerError, "Errore incontrato durante l'invio delle risposte")?;
+    }
+
+    Ok(ApiResponse {
+        status: Status::Created,
+        body: SubmissionsBatchPostResponse { submissions: results },
+        headers: HeaderMap::new(),
     })
 }
 
@@ -243,11 +559,50 @@ pub async fn patch_submission(
 }
 
 #[delete("/contests/<id>/submissions/<submission_id>")]
-pub async fn delete_submission(
+pub async fn delete_submission<'r>(
     id: i32,
     submission_id: i32,
-    mut db: Connection<DB>
-) -> Status {
-    Status::NotImplemented
+    mut db: Connection<DB>,
+    api_user: ApiUser,
+) -> Result<ApiResponse<'r, ()>, ApiResponse<'r, ApiError>> {
+    use crate::schema::{contests, teams, submissions};
+
+    // Only the answers of the teams of the user can be taken back
+    let owned = submissions::dsl::submissions
+        .inner_join(teams::table.inner_join(contests::table))
+        .select(submissions::id)
+        .filter(submissions::id.eq(submission_id))
+        .filter(teams::contest_id.eq(id))
+        .filter(teams::is_fake.eq(false))
+        .filter(contests::owner_id.eq(api_user.user_id))
+        .filter(contests::active.eq(true))
+        .load::<i32>(&mut **db)
+        .await
+        .attach_info(Status::InternalServerError, "Errore incontrato durante l'eliminazione della risposta")?;
+
+    if owned.is_empty() {
+        return Err(ApiResponse {
+            status: Status::NotFound,
+            body: ApiError { error: "La sottoposizione non esiste o non ti appartiene".to_string() },
+            headers: HeaderMap::new(),
+        });
+    }
+
+    diesel::delete(submissions::dsl::submissions)
+        .filter(submissions::id.eq(submission_id))
+        .execute(&mut **db)
+        .await
+        .attach_info(Status::InternalServerError, "Errore incontrato durante l'eliminazione della risposta")?;
+
+    // The final ranking, if the contest is over, no longer matches the answers
+    delete_final_ranking(&mut db, id)
+        .await
+        .attach_info(Status::InternalServerError, "Errore incontrato durante l'eliminazione della risposta")?;
+
+    Ok(ApiResponse {
+        status: Status::NoContent,
+        body: (),
+        headers: HeaderMap::new(),
+    })
 }
 
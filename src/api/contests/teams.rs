@@ -8,6 +8,10 @@ use serde::{Deserialize, Serialize};
 use validator::Validate;
 
 use crate::api::{prop_error, ApiUser};
+use crate::contest::bots::{add_bots, random_seed};
+use crate::contest::ghosts::copy_ghost;
+use crate::contest::scorecard::{fetch_scorecard, Scorecard};
+use crate::contest::selection::{apply_team_selection, TeamSelection};
 use crate::model::Team;
 use crate::DB;
 use crate::error::IntoStatusResult;
@@ -23,6 +27,9 @@ pub struct TeamGetResponse {
     id: i32,
     team_name: String,
     is_fake: bool,
+    hidden: bool,
+    is_bot: bool,
+    is_ghost: bool,
 }
 
 #[derive(Deserialize, Validate)]
@@ -36,6 +43,49 @@ pub struct TeamPostResponse {
     team_id: i32,
 }
 
+#[derive(Deserialize)]
+pub struct TeamSelectionPutData {
+    #[serde(flatten)]
+    selection: TeamSelection,
+    #[serde(default)]
+    count_for_drift: bool,
+}
+
+#[derive(Serialize)]
+pub struct TeamSelectionPutResponse {
+    selected: usize,
+}
+
+fn default_bots_count() -> u8 {
+    1
+}
+
+#[derive(Deserialize, Validate)]
+pub struct BotsPostData<'r> {
+    #[validate(range(max = 100))]
+    percentile: u8,
+    #[serde(default = "default_bots_count")]
+    #[validate(range(min = 1, max = 20))]
+    count: u8,
+    seed: Option<u64>,
+    #[validate(length(max = 30))]
+    name: Option<&'r str>,
+}
+
+#[derive(Deserialize, Validate)]
+pub struct GhostPostData<'r> {
+    source_contest: i32,
+    team_id: i32,
+    #[validate(length(max = 35))]
+    team_name: Option<&'r str>,
+}
+
+#[derive(Serialize)]
+pub struct BotsPostResponse {
+    team_ids: Vec<i32>,
+    seed: u64,
+}
+
 #[get("/contests/<id>/teams")]
 pub async fn get_teams<'r>(
     id: i32,
@@ -138,6 +188,10 @@ pub async fn post_team<'r>(
             contest_id: id,
             is_fake: false,
             position: team_no as i32,
+            phiquadro_sess: None,
+            hidden: false,
+            is_bot: false,
+            is_ghost: false,
         })
         .returning(teams::id)
         .get_result(&mut **db)
@@ -165,7 +219,7 @@ pub async fn get_team<'r>(
 
     let teams = teams::dsl::teams
         .inner_join(contests::table)
-        .select((teams::id, teams::team_name, teams::is_fake))
+        .select((teams::id, teams::team_name, teams::is_fake, teams::hidden, teams::is_bot, teams::is_ghost))
         .filter(teams::id.eq(team_id))
         .filter(teams::contest_id.eq(id))
         .filter(contests::owner_id.eq(api_user.user_id))
@@ -188,6 +242,30 @@ pub async fn get_team<'r>(
     }
 }
 
+#[get("/contests/<id>/teams/<team_id>/scorecard")]
+pub async fn get_team_scorecard<'r>(
+    id: i32,
+    team_id: i32,
+    mut db: Connection<DB>,
+    api_user: ApiUser,
+) -> Result<ApiResponse<'r, Scorecard>, ApiResponse<'r, ApiError>> {
+    match fetch_scorecard(&mut db, api_user.user_id, id, team_id)
+        .await
+        .attach_info(Status::InternalServerError, "Errore riscontrato durante l'operazione")?
+    {
+        Some(scorecard) => Ok(ApiResponse {
+            status: Status::Ok,
+            body: scorecard,
+            headers: HeaderMap::new(),
+        }),
+        None => Err(ApiResponse {
+            status: Status::NotFound,
+            body: ApiError { error: "La squadra non esiste o non ti appartiene".to_string() },
+            headers: HeaderMap::new(),
+        }),
+    }
+}
+
 #[delete("/contests/<id>/teams/<team_id>")]
 pub async fn delete_team<'r>(
     id: i32,
@@ -195,7 +273,7 @@ pub async fn delete_team<'r>(
     mut db: Connection<DB>,
     api_user: ApiUser,
 ) -> Result<ApiResponse<'r, ()>, ApiResponse<'r, ApiError>> {
-    use crate::schema::{contests, teams};
+    use crate::schema::{contests, jollies, submissions, teams};
 
     let exists: i64 = contests::dsl::contests
         .filter(contests::id.eq(id))
@@ -214,15 +292,33 @@ pub async fn delete_team<'r>(
         });
     }
 
-    let pos = diesel::delete(teams::dsl::teams)
+    // The answers of the team go away with it
+    let owned_team = teams::dsl::teams
+        .select(teams::id)
+        .filter(teams::id.eq(team_id))
+        .filter(teams::contest_id.eq(id));
+
+    diesel::delete(submissions::dsl::submissions)
+        .filter(submissions::team_id.eq_any(owned_team))
+        .execute(&mut **db)
+        .await
+        .attach_info(Status::InternalServerError, "Errore incontrato durante l'eliminazione della squadra")?;
+
+    diesel::delete(jollies::dsl::jollies)
+        .filter(jollies::team_id.eq_any(owned_team))
+        .execute(&mut **db)
+        .await
+        .attach_info(Status::InternalServerError, "Errore incontrato durante l'eliminazione della squadra")?;
+
+    let deleted = diesel::delete(teams::dsl::teams)
         .filter(teams::id.eq(team_id))
         .filter(teams::contest_id.eq(id))
-        .returning(teams::position)
-        .load::<i32>(&mut **db)
+        .returning((teams::position, teams::is_fake))
+        .load::<(i32, bool)>(&mut **db)
         .await
         .attach_info(Status::InternalServerError, "Errore incontrato durante l'eliminazione della squadra")?;
 
-    let Some(&pos) = pos.get(0) else {
+    let Some(&(pos, is_fake)) = deleted.get(0) else {
         return Err(ApiResponse {
             status: Status::NotFound,
             body: ApiError { error: "La squadra non esiste".to_string(), },
@@ -230,6 +326,15 @@ pub async fn delete_team<'r>(
         });
     };
 
+    if is_fake {
+        diesel::update(contests::dsl::contests)
+            .filter(contests::id.eq(id))
+            .set(contests::teams_no.eq(contests::teams_no - 1))
+            .execute(&mut **db)
+            .await
+            .attach_info(Status::InternalServerError, "Errore incontrato durante l'eliminazione della squadra")?;
+    }
+
     let max_pos = teams::dsl::teams
         .select(max(teams::position))
         .filter(teams::contest_id.eq(id))
@@ -261,3 +366,114 @@ pub async fn delete_team<'r>(
 
     Ok(resp)
 }
+
+#[put("/contests/<id>/teams/selection", format = "application/json", data = "<data>")]
+pub async fn put_team_selection<'r>(
+    id: i32,
+    data: ApiInputResult<'r, TeamSelectionPutData>,
+    mut db: Connection<DB>,
+    api_user: ApiUser,
+) -> Result<ApiResponse<'r, TeamSelectionPutResponse>, ApiResponse<'r, ApiError>> {
+    let Ok(data) = data else {
+        return Err(ApiResponse {
+            status: Status::BadRequest,
+            body: ApiError { error: "Richiesta malformata".to_string() },
+            headers: HeaderMap::new(),
+        });
+    };
+
+    let selected = apply_team_selection(&mut db, api_user.user_id, id, &data.selection, data.count_for_drift).await?;
+
+    Ok(ApiResponse {
+        status: Status::Ok,
+        body: TeamSelectionPutResponse { selected },
+        headers: HeaderMap::new(),
+    })
+}
+
+#[post("/contests/<id>/bots", format = "application/json", data = "<bots>")]
+pub async fn post_bots<'r>(
+    id: i32,
+    bots: ApiInputResult<'r, BotsPostData<'r>>,
+    mut db: Connection<DB>,
+    api_user: ApiUser,
+) -> Result<ApiResponse<'r, BotsPostResponse>, ApiResponse<'r, ApiError>> {
+    let Ok(bots) = bots else {
+        return Err(ApiResponse {
+            status: Status::BadRequest,
+            body: ApiError { error: "Richiesta malformata".to_string() },
+            headers: HeaderMap::new(),
+        });
+    };
+
+    bots
+        .validate()
+        .map_err(|err| prop_error(
+            err,
+            Status::UnprocessableEntity,
+            "Il percentile deve essere tra 0 e 100, i bot tra 1 e 20 e il nome di al più 30 caratteri."
+        ))?;
+
+    let seed = match bots.seed {
+        Some(seed) => seed,
+        None => random_seed()?,
+    };
+
+    let team_ids = add_bots(
+        &mut db,
+        api_user.user_id,
+        id,
+        bots.percentile,
+        bots.count as usize,
+        seed,
+        bots.name,
+    ).await?;
+
+    Ok(ApiResponse {
+        status: Status::Created,
+        body: BotsPostResponse { team_ids, seed },
+        headers: HeaderMap::new(),
+    })
+}
+
+#[post("/contests/<id>/ghosts", format = "application/json", data = "<ghost>")]
+pub async fn post_ghost<'r>(
+    id: i32,
+    ghost: ApiInputResult<'r, GhostPostData<'r>>,
+    mut db: Connection<DB>,
+    api_user: ApiUser,
+) -> Result<ApiResponse<'r, TeamPostResponse>, ApiResponse<'r, ApiError>> {
+    let Ok(ghost) = ghost else {
+        return Err(ApiResponse {
+            status: Status::BadRequest,
+            body: ApiError { error: "Richiesta malformata".to_string() },
+            headers: HeaderMap::new(),
+        });
+    };
+
+    ghost
+        .validate()
+        .map_err(|err| prop_error(
+            err,
+            Status::UnprocessableEntity,
+            "Assicurati che il nome della squadra sia di al più 35 caratteri."
+        ))?;
+
+    let team_id = copy_ghost(
+        &mut db,
+        api_user.user_id,
+        id,
+        ghost.source_contest,
+        ghost.team_id,
+        ghost.team_name,
+    ).await?;
+
+    let mut headers = HeaderMap::new();
+    headers.add(Header::new(header::LOCATION.as_str(), format!("/contest/{id}/teams/{team_id}")));
+
+    Ok(ApiResponse {
+        status: Status::Created,
+        body: TeamPostResponse { team_id },
+        headers,
+    })
+}
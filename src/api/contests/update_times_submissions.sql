@@ -1,4 +1,4 @@
 UPDATE submissions
-    SET sub_time = sub_time + $1
+    SET sub_time = $1 + (sub_time - $2) * $3
 FROM questions
-    WHERE questions.id = submissions.question_id AND contest_id = $2
+    WHERE questions.id = submissions.question_id AND contest_id = $4
@@ -62,6 +62,39 @@ impl<'r> FromRequest<'r> for ApiUser {
     }
 }
 
+/// The longest idempotency key accepted, as long as the column storing it
+const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 255;
+
+/// The key sent by a client in the `Idempotency-Key` header, so that retrying a request doesn't repeat its effects
+pub struct IdempotencyKey(pub Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IdempotencyKey {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(IdempotencyKey(req.headers().get_one("Idempotency-Key").map(str::to_string)))
+    }
+}
+
+impl IdempotencyKey {
+    pub fn validate<'r>(&self) -> Result<(), ApiResponse<'r, ApiError>> {
+        match &self.0 {
+            Some(key) if key.is_empty() || key.len() > MAX_IDEMPOTENCY_KEY_LENGTH => Err(ApiResponse {
+                status: Status::UnprocessableEntity,
+                body: ApiError {
+                    error: format!(
+                        "La chiave di idempotenza deve essere lunga da 1 a {} caratteri",
+                        MAX_IDEMPOTENCY_KEY_LENGTH,
+                    ),
+                },
+                headers: HeaderMap::new(),
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct ApiResponse<'r, T> {
     pub status: Status,
@@ -134,6 +167,11 @@ pub async fn patch_api_unauthorized() -> &'static ApiResponse<'static, ApiError>
     &*UNAUTHORIZED_RESPONSE
 }
 
+#[put("/<_..>", rank = 2)]
+pub async fn put_api_unauthorized() -> &'static ApiResponse<'static, ApiError> {
+    &*UNAUTHORIZED_RESPONSE
+}
+
 #[delete("/<_..>", rank = 2)]
 pub async fn delete_api_unauthorized() -> &'static ApiResponse<'static, ApiError> {
     &*UNAUTHORIZED_RESPONSE
@@ -144,25 +182,48 @@ pub fn routes() -> Vec<Route> {
         get_api_unauthorized,
         post_api_unauthorized,
         patch_api_unauthorized,
+        put_api_unauthorized,
         delete_api_unauthorized,
         contests::get_contest,
         contests::get_contests,
+        contests::get_contest_series,
+        contests::get_contest_report,
+        contests::get_contest_leaderboard,
+        contests::put_contest_leaderboard,
         contests::post_contest,
+        contests::post_contest_import,
         contests::patch_contest,
+        contests::post_contest_pause,
+        contests::post_contest_resume,
+        contests::post_contest_restart,
+        contests::post_contest_spectator,
+        contests::delete_contest_spectator,
+        contests::post_contest_reveal,
+        contests::post_contest_final_ranking,
         contests::delete_contest,
         contests::teams::get_team,
         contests::teams::get_teams,
         contests::teams::post_team,
         contests::teams::delete_team,
+        contests::teams::put_team_selection,
+        contests::teams::post_bots,
+        contests::teams::post_ghost,
+        contests::teams::get_team_scorecard,
         contests::submissions::get_submission,
         contests::submissions::get_submissions,
         contests::submissions::post_submission,
+        contests::submissions::post_submissions_batch,
         contests::submissions::patch_submission,
         contests::submissions::delete_submission,
         contests::jollies::get_jolly,
         contests::jollies::get_jollies,
         contests::jollies::post_jolly,
         contests::jollies::delete_jolly,
+        contests::questions::get_question_stats,
+        contests::exports::get_ranking_csv,
+        contests::exports::get_submissions_csv,
+        contests::exports::get_results_pdf,
+        contests::exports::get_results_zip,
         login::signup,
         login::login,
     ]
@@ -0,0 +1,198 @@
+use chrono::{DateTime, Datelike, TimeZone, Timelike};
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x04034b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x02014b50;
+const END_SIGNATURE: u32 = 0x06054b50;
+/// Version 2.0 of the specification, the first one readers must support
+const VERSION: u16 = 20;
+/// The names of the files are UTF-8
+const UTF8_FLAG: u16 = 1 << 11;
+
+/// The CRC-32 checksum, as zip files use it
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb88320 } else { crc >> 1 };
+        }
+    }
+
+    !crc
+}
+
+/// The MS-DOS time and date zip files store the modification time as
+fn dos_time<Tz: TimeZone>(time: &DateTime<Tz>) -> (u16, u16) {
+    let dos_time = ((time.hour() << 11) | (time.minute() << 5) | (time.second() / 2)) as u16;
+    let dos_date = (((time.year() - 1980).clamp(0, 127) as u32) << 9) | (time.month() << 5) | time.day();
+
+    (dos_time, dos_date as u16)
+}
+
+/// A zip archive whose files are stored without compression, enough for files which are already compact like PDFs
+#[derive(Clone, Debug, Default)]
+pub struct Archive {
+    data: Vec<u8>,
+    central_directory: Vec<u8>,
+    files: u16,
+}
+
+impl Archive {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a file with the given path and content, modified at the given time
+    pub fn add<Tz: TimeZone>(&mut self, name: &str, content: &[u8], modified: &DateTime<Tz>) {
+        let (time, date) = dos_time(modified);
+        let crc = crc32(content);
+        let offset = self.data.len() as u32;
+
+        let mut header = vec![];
+        header.extend(VERSION.to_le_bytes());
+        header.extend(UTF8_FLAG.to_le_bytes());
+        // No compression
+        header.extend(0u16.to_le_bytes());
+        header.extend(time.to_le_bytes());
+        header.extend(date.to_le_bytes());
+        header.extend(crc.to_le_bytes());
+        header.extend((content.len() as u32).to_le_bytes());
+        header.extend((content.len() as u32).to_le_bytes());
+        header.extend((name.len() as u16).to_le_bytes());
+        // No extra field
+        header.extend(0u16.to_le_bytes());
+
+        self.data.extend(LOCAL_HEADER_SIGNATURE.to_le_bytes());
+        self.data.extend(&header);
+        self.data.extend(name.as_bytes());
+        self.data.extend(content);
+
+        self.central_directory.extend(CENTRAL_HEADER_SIGNATURE.to_le_bytes());
+        self.central_directory.extend(VERSION.to_le_bytes());
+        self.central_directory.extend(&header);
+        // No comment, first disk, no attributes
+        self.central_directory.extend(0u16.to_le_bytes());
+        self.central_directory.extend(0u16.to_le_bytes());
+        self.central_directory.extend(0u16.to_le_bytes());
+        self.central_directory.extend(0u32.to_le_bytes());
+        self.central_directory.extend(offset.to_le_bytes());
+        self.central_directory.extend(name.as_bytes());
+
+        self.files += 1;
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        let offset = self.data.len() as u32;
+        let size = self.central_directory.len() as u32;
+        self.data.append(&mut self.central_directory);
+
+        self.data.extend(END_SIGNATURE.to_le_bytes());
+        // Single disk
+        self.data.extend(0u16.to_le_bytes());
+        self.data.extend(0u16.to_le_bytes());
+        self.data.extend(self.files.to_le_bytes());
+        self.data.extend(self.files.to_le_bytes());
+        self.data.extend(size.to_le_bytes());
+        self.data.extend(offset.to_le_bytes());
+        // No comment
+        self.data.extend(0u16.to_le_bytes());
+
+        self.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+
+    fn u16_at(data: &[u8], pos: usize) -> u16 {
+        u16::from_le_bytes(data[pos..pos + 2].try_into().unwrap())
+    }
+
+    fn u32_at(data: &[u8], pos: usize) -> u32 {
+        u32::from_le_bytes(data[pos..pos + 4].try_into().unwrap())
+    }
+
+    /// Reads the files of an archive without comment through its central directory, checking that each one matches
+    /// its local header and its checksum
+    fn read_archive(data: &[u8]) -> Vec<(String, Vec<u8>, (u16, u16))> {
+        let end = data.len() - 22;
+        assert_eq!(u32_at(data, end), END_SIGNATURE);
+        let files = u16_at(data, end + 10) as usize;
+        let size = u32_at(data, end + 12) as usize;
+        let mut pos = u32_at(data, end + 16) as usize;
+        assert_eq!(pos + size, end);
+
+        let mut read = vec![];
+        for _ in 0..files {
+            assert_eq!(u32_at(data, pos), CENTRAL_HEADER_SIGNATURE);
+            let (time, date) = (u16_at(data, pos + 12), u16_at(data, pos + 14));
+            let crc = u32_at(data, pos + 16);
+            let length = u32_at(data, pos + 24) as usize;
+            let name_length = u16_at(data, pos + 28) as usize;
+            let offset = u32_at(data, pos + 42) as usize;
+            let name = &data[pos + 46..pos + 46 + name_length];
+
+            assert_eq!(u32_at(data, offset), LOCAL_HEADER_SIGNATURE);
+            // The local header repeats the fields of the central one from the version needed on
+            assert_eq!(data[offset + 4..offset + 30], data[pos + 6..pos + 32]);
+            assert_eq!(&data[offset + 30..offset + 30 + name_length], name);
+            let content = &data[offset + 30 + name_length..offset + 30 + name_length + length];
+            assert_eq!(crc32(content), crc);
+
+            read.push((String::from_utf8(name.to_vec()).unwrap(), content.to_vec(), (time, date)));
+            pos += 46 + name_length;
+        }
+        assert_eq!(pos, end);
+
+        read
+    }
+
+    #[test]
+    fn crc32_matches_known_values() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+        assert_eq!(crc32(b"The quick brown fox jumps over the lazy dog"), 0x414fa339);
+    }
+
+    #[test]
+    fn dos_time_packs_date_and_time() {
+        let time = Utc.with_ymd_and_hms(2026, 10, 19, 14, 35, 59).unwrap();
+        assert_eq!(dos_time(&time), ((14 << 11) | (35 << 5) | 29, (46 << 9) | (10 << 5) | 19));
+
+        let before_dos = Utc.with_ymd_and_hms(1970, 1, 1, 0, 0, 0).unwrap();
+        assert_eq!(dos_time(&before_dos), (0, (1 << 5) | 1));
+    }
+
+    #[test]
+    fn empty_archive_has_only_the_end_record() {
+        let data = Archive::new().finish();
+        assert_eq!(data.len(), 22);
+        assert!(read_archive(&data).is_empty());
+    }
+
+    #[test]
+    fn files_round_trip() {
+        let time = Utc.with_ymd_and_hms(2026, 10, 19, 9, 0, 0).unwrap();
+        let files: Vec<(&str, Vec<u8>)> = vec![
+            ("classifica.pdf", b"%PDF-1.4\n".to_vec()),
+            ("attestati/01_Città.pdf", (0..=255).collect()),
+            ("vuoto.txt", vec![]),
+        ];
+
+        let mut archive = Archive::new();
+        for (name, content) in &files {
+            archive.add(name, content, &time);
+        }
+
+        let read = read_archive(&archive.finish());
+        assert_eq!(read.len(), files.len());
+        for ((name, content, modified), (expected_name, expected_content)) in read.iter().zip(&files) {
+            assert_eq!(name, expected_name);
+            assert_eq!(content, expected_content);
+            assert_eq!(*modified, dos_time(&time));
+        }
+    }
+}
@@ -0,0 +1,383 @@
+use std::cmp;
+use std::collections::HashMap;
+
+use anyhow::anyhow;
+use chrono::Utc;
+use diesel::{update, ExpressionMethods, QueryDsl};
+use rocket::http::Status;
+use rocket_db_pools::diesel::prelude::RunQueryDsl;
+use rocket_db_pools::Connection;
+use tracing::info;
+
+use super::contest::Contest;
+use super::fetch::{fetch_activity, fetch_contest};
+use super::import::{insert_team_activity, TeamActivity};
+use super::ranking::compute_ranking;
+use crate::model::{real_to_contest_time, ContestJollies, ContestSubmissions, Team};
+use crate::error::{IntoStatusResult, Result};
+use crate::DB;
+
+/// How many minutes the submissions of a bot can be moved from the ones of the team they are copied from
+const JITTER_MINUTES: i64 = 2;
+
+/// A small pseudo random number generator (SplitMix64), so that the same seed always generates the same bots
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..n`
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// A number in `lo..=hi`
+    fn between(&mut self, lo: i64, hi: i64) -> i64 {
+        lo + (self.next_u64() % (hi - lo + 1) as u64) as i64
+    }
+}
+
+/// Generates the activity of `count` bots playing like the real teams around the given percentile of the final
+/// ranking. For each question a bot copies, moved by a small delay, what a random team close to its percentile did,
+/// so it solves questions with the probability and the timing of those teams. The jolly is copied from one of these
+/// teams, which is also the one copied on the question of the jolly.
+///
+/// The teams of the contest must be ordered by position and the submissions by time.
+pub fn generate_bots(
+    mut contest: Contest,
+    submissions: &[ContestSubmissions],
+    jollies: &[ContestJollies],
+    percentile: u8,
+    count: usize,
+    seed: u64,
+) -> anyhow::Result<Vec<TeamActivity>> {
+    // Only the real teams of the original contest are used as a reference
+    for team in contest.teams.iter_mut() {
+        team.hidden = !team.is_real();
+    }
+    contest.hidden_drift = false;
+
+    let positions: HashMap<i32, usize> = contest.teams.iter().enumerate().map(|(pos, team)| (team.id, pos)).collect();
+    let end = contest.end_time();
+    compute_ranking(&mut contest, submissions, jollies, end);
+
+    let reference: Vec<usize> = contest.teams
+        .iter()
+        .filter(|team| !team.hidden)
+        .map(|team| positions[&team.id])
+        .collect();
+
+    if reference.is_empty() {
+        return Err(anyhow!("Contest {} has no real teams", contest.id));
+    }
+
+    // The teams around the percentile, best first
+    let n = reference.len();
+    let center = ((100 - percentile as usize) * (n - 1) + 50) / 100;
+    let radius = cmp::max(2, n / 20);
+    let window = &reference[center.saturating_sub(radius)..cmp::min(n, center + radius + 1)];
+
+    let last_minute = cmp::max(contest.duration.num_minutes() - 1, 0);
+    let mut activities: Vec<Vec<Vec<(i64, i32)>>> = vec![vec![vec![]; contest.questions.len()]; contest.teams.len()];
+    for submission in submissions {
+        let sub_time = real_to_contest_time(submission.sub_time - contest.start_time, contest.time_scale);
+        activities[submission.team_pos as usize][submission.question_pos as usize]
+            .push((sub_time.num_minutes(), submission.given_answer));
+    }
+
+    let mut team_jollies = vec![None; contest.teams.len()];
+    for jolly in jollies {
+        team_jollies[jolly.team_pos as usize] = Some(jolly.question_pos as usize);
+    }
+
+    let mut rng = SplitMix64(seed);
+    let mut bots = vec![];
+    for _ in 0..count {
+        let jolly_team = window[rng.below(window.len())];
+        let mut bot = TeamActivity { submissions: vec![], jolly: team_jollies[jolly_team] };
+
+        let sources: Vec<usize> = (0..contest.questions.len())
+            .map(|question| match bot.jolly {
+                Some(jolly) if jolly == question => jolly_team,
+                _ => window[rng.below(window.len())],
+            })
+            .collect();
+
+        for (question, &team) in sources.iter().enumerate() {
+            // The same delay for all the submissions to a question keeps them in the order the team gave them
+            let delay = rng.between(-JITTER_MINUTES, JITTER_MINUTES);
+            for &(sub_time, answer) in &activities[team][question] {
+                bot.submissions.push(((sub_time + delay).clamp(0, last_minute), answer, question));
+            }
+        }
+        // The sort is stable, so the submissions to a question which end up in the same minute keep their order
+        bot.submissions.sort_by_key(|&(sub_time, _, _)| sub_time);
+
+        bots.push(bot);
+    }
+
+    Ok(bots)
+}
+
+/// Adds to a contest which has not started yet `count` bots at the given percentile, generated from `seed`, and
+/// returns their ids
+pub async fn add_bots(
+    db: &mut Connection<DB>,
+    user_id: i32,
+    id: i32,
+    percentile: u8,
+    count: usize,
+    seed: u64,
+    name: Option<&str>,
+) -> Result<Vec<i32>> {
+    use crate::schema::{contests, teams};
+
+    let Some(contest) = fetch_contest(db, user_id, id)
+        .await
+        .attach_info(Status::InternalServerError, "Errore incontrato durante la creazione dei bot")?
+    else {
+        return Err(anyhow!("Contest {} not found for user {}", id, user_id))
+            .attach_info(Status::NotFound, "La gara non esiste o non ti appartiene");
+    };
+
+    if contest.join_time() <= Utc::now() {
+        return Err(anyhow!("Contest {} has already started", id))
+            .attach_info(Status::Forbidden, "La gara è già iniziata");
+    }
+
+    info!("Adding {} bots at percentile {} to contest {} with seed {}", count, percentile, id, seed);
+
+    let (submissions, jollies) = fetch_activity(db, id, contest.end_time())
+        .await
+        .attach_info(Status::InternalServerError, "Errore incontrato durante la creazione dei bot")?;
+
+    let questions: Vec<i32> = contest.questions.iter().map(|question| question.id).collect();
+    let (start_time, time_scale, first_pos) = (contest.start_time, contest.time_scale, contest.teams.len());
+
+    let bots = generate_bots(contest, &submissions, &jollies, percentile, count, seed)
+        .attach_info(Status::UnprocessableEntity, "Non ci sono squadre reali da cui ricavare i bot")?;
+
+    let base_name = match name {
+        Some(name) if !name.trim().is_empty() => name.trim().to_string(),
+        _ => format!("Bot {}° percentile", percentile),
+    };
+
+    let team_ids = diesel::insert_into(teams::table)
+        .values(
+            (0..count)
+                .map(|i| Team {
+                    team_name: if count > 1 { format!("{} #{}", base_name, i + 1) } else { base_name.clone() },
+                    is_fake: true,
+                    position: (first_pos + i) as i32,
+                    contest_id: id,
+                    phiquadro_sess: None,
+                    hidden: false,
+                    is_bot: true,
+                    is_ghost: false,
+                })
+                .collect::<Vec<_>>(),
+        )
+        .returning(teams::id)
+        .get_results::<i32>(db)
+        .await
+        .attach_info(Status::InternalServerError, "Errore incontrato durante la creazione dei bot")?;
+
+    for (bot, &team_id) in bots.iter().zip(&team_ids) {
+        insert_team_activity(db, team_id, bot, &questions, start_time, time_scale)
+            .await
+            .attach_info(Status::InternalServerError, "Errore incontrato durante la creazione dei bot")?;
+    }
+
+    update(contests::dsl::contests.filter(contests::id.eq(id)))
+        .set(contests::teams_no.eq(contests::teams_no + count as i32))
+        .execute(db)
+        .await
+        .attach_info(Status::InternalServerError, "Errore incontrato durante la creazione dei bot")?;
+
+    Ok(team_ids)
+}
+
+/// Picks a seed for bots when the user doesn't choose one. It is kept below 2^53 so that it survives a round trip
+/// through a JavaScript number
+pub fn random_seed() -> Result<u64> {
+    use ring::rand::{SecureRandom, SystemRandom};
+
+    let mut bytes = [0u8; 8];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| anyhow!("Could not generate a random seed"))
+        .attach_info(Status::InternalServerError, "Errore incontrato durante la creazione dei bot")?;
+
+    Ok(u64::from_le_bytes(bytes) & ((1 << 53) - 1))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+
+    use super::*;
+    use crate::contest::contest::{Question, Team, TeamQuestion};
+
+    const QUESTIONS: usize = 4;
+    const MINUTES: i64 = 120;
+
+    fn team(pos: usize, is_fake: bool, is_bot: bool) -> Team {
+        Team {
+            id: pos as i32 + 1,
+            name: format!("Squadra {}", pos + 1),
+            is_fake,
+            phiquadro_sess: None,
+            hidden: false,
+            is_bot,
+            is_ghost: false,
+            score: 10 * QUESTIONS as i64,
+            questions: vec![TeamQuestion::default(); QUESTIONS],
+        }
+    }
+
+    fn contest(teams: Vec<Team>) -> Contest {
+        Contest {
+            id: 1,
+            name: "Gara".to_string(),
+            phiquadro_id: None,
+            phiquadro_sess: None,
+            phiquadro_sessions: vec![],
+            questions: (0..QUESTIONS)
+                .map(|i| Question {
+                    id: i as i32 + 1,
+                    answer: 1,
+                    score: 20,
+                    locked: false,
+                    lock_time: Duration::zero(),
+                })
+                .collect(),
+            teams,
+            duration: Duration::minutes(MINUTES),
+            start_time: Utc.with_ymd_and_hms(2025, 3, 1, 10, 0, 0).unwrap(),
+            drift: 4,
+            drift_time: Duration::minutes(20),
+            jolly_time: Duration::minutes(10),
+            question_bonus: vec![20, 15, 10, 8, 6, 5, 4, 3, 2, 1],
+            contest_bonus: vec![100, 60, 40, 30, 20, 10, 0, 0, 0, 0],
+            hidden_drift: false,
+            time_scale: 1.0,
+            paused_at: None,
+            start_offset: Duration::zero(),
+            owner_id: 1,
+            freeze_time: None,
+            revealed: false,
+        }
+    }
+
+    /// Every team answers every question, at the first and the last minute and once in between, with an answer which
+    /// tells the team and the question apart. Each team chooses a different question as jolly
+    fn activity(contest: &Contest) -> (Vec<ContestSubmissions>, Vec<ContestJollies>) {
+        let mut submissions = vec![];
+        for team_pos in 0..contest.teams.len() {
+            for question_pos in 0..QUESTIONS {
+                for minute in [0, 10 * question_pos as i64 + team_pos as i64 + 5, MINUTES - 1] {
+                    submissions.push(ContestSubmissions {
+                        given_answer: 100 * team_pos as i32 + question_pos as i32,
+                        sub_time: contest.real_time(Duration::minutes(minute)),
+                        correct_answer: 1,
+                        question_pos: question_pos as i32,
+                        team_pos: team_pos as i32,
+                        is_fake: true,
+                        contest_id: contest.id,
+                    });
+                }
+            }
+        }
+        submissions.sort_by_key(|submission| submission.sub_time);
+
+        let jollies = (0..contest.teams.len())
+            .map(|team_pos| ContestJollies {
+                sub_time: contest.real_time(Duration::minutes(1)),
+                question_pos: (team_pos % QUESTIONS) as i32,
+                team_pos: team_pos as i32,
+                contest_id: contest.id,
+            })
+            .collect();
+
+        (submissions, jollies)
+    }
+
+    fn bots(teams: Vec<Team>, count: usize, seed: u64) -> anyhow::Result<Vec<TeamActivity>> {
+        let contest = contest(teams);
+        let (submissions, jollies) = activity(&contest);
+        generate_bots(contest, &submissions, &jollies, 50, count, seed)
+    }
+
+    fn real_teams() -> Vec<Team> {
+        (0..QUESTIONS).map(|pos| team(pos, true, false)).collect()
+    }
+
+    #[test]
+    fn split_mix_64_is_deterministic() {
+        let mut a = SplitMix64(7);
+        let mut b = SplitMix64(7);
+        let mut c = SplitMix64(8);
+        let a: Vec<u64> = (0..8).map(|_| a.next_u64()).collect();
+        assert_eq!(a, (0..8).map(|_| b.next_u64()).collect::<Vec<_>>());
+        assert_ne!(a, (0..8).map(|_| c.next_u64()).collect::<Vec<_>>());
+
+        let mut rng = SplitMix64(7);
+        for _ in 0..1000 {
+            assert!(rng.below(3) < 3);
+            assert!((-2..=2).contains(&rng.between(-2, 2)));
+        }
+    }
+
+    #[test]
+    fn same_seed_same_bots() {
+        let first = bots(real_teams(), 5, 42).unwrap();
+        let second = bots(real_teams(), 5, 42).unwrap();
+        assert_eq!(first.len(), 5);
+        assert_eq!(format!("{:?}", first), format!("{:?}", second));
+    }
+
+    #[test]
+    fn different_seed_different_bots() {
+        let first = bots(real_teams(), 5, 42).unwrap();
+        let second = bots(real_teams(), 5, 43).unwrap();
+        assert_ne!(format!("{:?}", first), format!("{:?}", second));
+    }
+
+    #[test]
+    fn times_stay_in_the_contest() {
+        for seed in 0..20 {
+            for bot in bots(real_teams(), 5, seed).unwrap() {
+                assert_eq!(bot.submissions.len(), 3 * QUESTIONS);
+                assert!(bot.submissions.iter().all(|&(time, _, _)| (0..MINUTES).contains(&time)));
+                assert!(bot.submissions.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+            }
+        }
+    }
+
+    #[test]
+    fn jolly_question_copied_from_the_jolly_team() {
+        for seed in 0..20 {
+            for bot in bots(real_teams(), 5, seed).unwrap() {
+                // The team with the jolly on question `q` is the one in position `q`
+                let jolly = bot.jolly.unwrap();
+                assert!(bot.submissions
+                    .iter()
+                    .filter(|&&(_, _, question)| question == jolly)
+                    .all(|&(_, answer, _)| answer == 100 * jolly as i32 + jolly as i32));
+            }
+        }
+    }
+
+    #[test]
+    fn no_real_teams() {
+        let teams = vec![team(0, false, false), team(1, true, true)];
+        assert!(bots(teams, 1, 42).is_err());
+    }
+}
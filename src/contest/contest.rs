@@ -1,12 +1,16 @@
 use chrono::{Duration, DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use crate::model::contest_to_real_time;
+
 #[derive(Clone, Copy, Serialize, Deserialize)]
 pub struct Question {
     pub id: i32,
     pub answer: i32,
     pub score: i64,
     pub locked: bool,
+    /// The contest time at which the score of the question stops growing
+    pub lock_time: Duration,
 }
 
 #[derive(Clone, PartialEq, Eq, Copy, Default, Serialize, Deserialize)]
@@ -30,16 +34,30 @@ pub struct Team {
     pub id: i32,
     pub name: String,
     pub is_fake: bool,
+    pub phiquadro_sess: Option<i32>,
+    pub hidden: bool,
+    pub is_bot: bool,
+    /// Copied from a team of the users in another contest
+    pub is_ghost: bool,
     pub score: i64,
     pub questions: Vec<TeamQuestion>,
 }
 
+impl Team {
+    /// Whether the team took part in the original contest, rather than being added by the users, generated as a bot
+    /// or copied as a ghost
+    pub fn is_real(&self) -> bool {
+        self.is_fake && !self.is_bot && !self.is_ghost
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Contest {
     pub id: i32,
     pub name: String,
-    pub phiquadro_id: i32,
-    pub phiquadro_sess: i32,
+    pub phiquadro_id: Option<i32>,
+    pub phiquadro_sess: Option<i32>,
+    pub phiquadro_sessions: Vec<i32>,
     pub questions: Vec<Question>,
     pub teams: Vec<Team>,
     pub duration: Duration,
@@ -49,4 +67,30 @@ pub struct Contest {
     pub jolly_time: Duration,
     pub question_bonus: Vec<i32>,
     pub contest_bonus: Vec<i32>,
+    pub hidden_drift: bool,
+    pub time_scale: f64,
+    pub paused_at: Option<DateTime<Utc>>,
+    pub start_offset: Duration,
+    pub owner_id: i32,
+    /// The contest time after which the public ranking stops changing until it is revealed
+    pub freeze_time: Option<Duration>,
+    pub revealed: bool,
+}
+
+impl Contest {
+    /// The real time at which the given instant of contest time falls
+    pub fn real_time(&self, delta: Duration) -> DateTime<Utc> {
+        self.start_time + contest_to_real_time(delta, self.time_scale)
+    }
+
+    /// The real time at which the user teams join the contest, which is later than its start if the replay begins
+    /// from an offset
+    pub fn join_time(&self) -> DateTime<Utc> {
+        self.real_time(self.start_offset)
+    }
+
+    /// The real time at which the contest ends
+    pub fn end_time(&self) -> DateTime<Utc> {
+        self.real_time(self.duration)
+    }
 }
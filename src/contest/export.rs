@@ -0,0 +1,111 @@
+use std::borrow::Cow;
+
+use rocket_db_pools::Connection;
+
+use super::contest::{Contest, Team};
+use super::fetch::{fetch_activity, fetch_contest, fetch_contest_with_ranking, ranking_time};
+use crate::model::real_to_contest_time;
+use crate::DB;
+
+/// Lets spreadsheet programs know that the file is UTF-8, or they would garble the accented letters
+const BYTE_ORDER_MARK: &str = "\u{feff}";
+
+/// Quotes a field if it contains a separator, a quote or a line break, as RFC 4180 says
+fn csv_field(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+fn push_row<S: AsRef<str>>(csv: &mut String, fields: &[S]) {
+    let fields: Vec<Cow<str>> = fields.iter().map(|field| csv_field(field.as_ref())).collect();
+    csv.push_str(&fields.join(","));
+    csv.push_str("\r\n");
+}
+
+fn team_kind(team: &Team) -> &'static str {
+    if team.is_bot {
+        "Bot"
+    } else if team.is_ghost {
+        "Fantasma"
+    } else if team.is_fake {
+        "Reale"
+    } else {
+        "Utente"
+    }
+}
+
+/// Formats seconds of contest time as hours, minutes and seconds
+fn format_time(seconds: i64) -> String {
+    format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+/// The ranking of a contest, one row per team with its score on each question and the question of its jolly. The
+/// teams must be sorted by rank, as the ranking does
+pub fn ranking_csv(contest: &Contest) -> String {
+    let mut csv = BYTE_ORDER_MARK.to_string();
+
+    let mut header = vec!["Posizione".to_string(), "Squadra".to_string(), "Tipo".to_string(), "Totale".to_string()];
+    header.extend((1..=contest.questions.len()).map(|position| format!("Q. {}", position)));
+    header.push("Jolly".to_string());
+    push_row(&mut csv, &header);
+
+    for (i, team) in contest.teams.iter().enumerate() {
+        let mut row = vec![(i + 1).to_string(), team.name.clone(), team_kind(team).to_string(), team.score.to_string()];
+        row.extend(team.questions.iter().map(|question| question.score.to_string()));
+        row.push(
+            team.questions
+                .iter()
+                .position(|question| question.jolly)
+                .map_or(String::new(), |q_pos| (q_pos + 1).to_string()),
+        );
+        push_row(&mut csv, &row);
+    }
+
+    csv
+}
+
+/// Exports the ranking of a contest as it is shown. Returns `None` if the contest doesn't exist
+pub async fn fetch_ranking_csv(db: &mut Connection<DB>, user_id: i32, id: i32) -> anyhow::Result<Option<String>> {
+    let Some(contest) = fetch_contest_with_ranking(db, user_id, id).await? else {
+        return Ok(None);
+    };
+
+    Ok(Some(ranking_csv(&contest)))
+}
+
+/// Exports every answer given until now in a contest by the teams shown in the ranking, sorted by time. Returns
+/// `None` if the contest doesn't exist
+pub async fn fetch_submissions_csv(db: &mut Connection<DB>, user_id: i32, id: i32) -> anyhow::Result<Option<String>> {
+    let Some(contest) = fetch_contest(db, user_id, id).await? else {
+        return Ok(None);
+    };
+
+    let now = ranking_time(&contest);
+    let (submissions, _) = fetch_activity(db, id, now).await?;
+
+    let mut csv = BYTE_ORDER_MARK.to_string();
+    push_row(&mut csv, &["Tempo", "Secondi", "Squadra", "Tipo", "Quesito", "Risposta", "Corretta"]);
+
+    for submission in &submissions {
+        let team = &contest.teams[submission.team_pos as usize];
+        if team.hidden {
+            continue;
+        }
+
+        let seconds = real_to_contest_time(submission.sub_time - contest.start_time, contest.time_scale).num_seconds();
+        push_row(&mut csv, &[
+            format_time(seconds),
+            seconds.to_string(),
+            team.name.clone(),
+            team_kind(team).to_string(),
+            (submission.question_pos + 1).to_string(),
+            submission.given_answer.to_string(),
+            if submission.given_answer == submission.correct_answer { "sì" } else { "no" }.to_string(),
+        ]);
+    }
+
+    Ok(Some(csv))
+}
@@ -1,18 +1,25 @@
 use std::cmp;
 
-use chrono::TimeDelta;
+use chrono::{DateTime, TimeDelta, Utc};
 use diesel::{ExpressionMethods, QueryDsl};
 use rocket_db_pools::diesel::prelude::RunQueryDsl;
 use rocket_db_pools::Connection;
 use tracing::info;
 
 use super::contest::{Contest, Question, Team};
-use crate::contest::contest::{QuestionStatus, TeamQuestion};
+use super::ranking::compute_ranking;
+use super::snapshot::fetch_final_ranking;
+use crate::contest::contest::TeamQuestion;
 use crate::model::{self, ContestJollies, ContestSubmissions};
 
 use crate::DB;
 
 pub async fn fetch_contest(db: &mut Connection<DB>, user_id: i32, id: i32) -> anyhow::Result<Option<Contest>> {
+    Ok(fetch_contest_unchecked(db, id).await?.filter(|contest| contest.owner_id == user_id))
+}
+
+/// Loads a contest without checking who owns it, for the views which grant access in other ways
+pub async fn fetch_contest_unchecked(db: &mut Connection<DB>, id: i32) -> anyhow::Result<Option<Contest>> {
     use crate::schema::{contests, questions, teams};
 
     info!("Loading contest {}", id);
@@ -33,6 +40,12 @@ pub async fn fetch_contest(db: &mut Connection<DB>, user_id: i32, id: i32) -> an
             contests::question_bonus,
             contests::contest_bonus,
             contests::owner_id,
+            contests::hidden_drift,
+            contests::time_scale,
+            contests::paused_at,
+            contests::start_offset,
+            contests::freeze_time,
+            contests::revealed,
         ))
         .filter(contests::dsl::id.eq(id))
         .filter(contests::active.eq(true))
@@ -43,10 +56,6 @@ pub async fn fetch_contest(db: &mut Connection<DB>, user_id: i32, id: i32) -> an
         return Ok(None);
     };
 
-    if contest.owner_id != user_id {
-        return Ok(None);
-    }
-
     let questions = questions::dsl::questions
         .select((questions::id, questions::answer))
         .filter(questions::contest_id.eq(id))
@@ -61,8 +70,13 @@ pub async fn fetch_contest(db: &mut Connection<DB>, user_id: i32, id: i32) -> an
             teams::is_fake,
             teams::position,
             teams::contest_id,
+            teams::phiquadro_sess,
+            teams::hidden,
+            teams::is_bot,
+            teams::is_ghost,
         ))
         .filter(teams::contest_id.eq(id))
+        .order(teams::position.asc())
         .load::<model::TeamWithId>(db)
         .await?;
 
@@ -73,15 +87,29 @@ pub async fn fetch_contest(db: &mut Connection<DB>, user_id: i32, id: i32) -> an
             answer,
             score: 20,
             locked: false,
+            lock_time: TimeDelta::zero(),
         })
         .collect();
 
+    let mut phiquadro_sessions: Vec<i32> = teams
+        .iter()
+        .filter_map(|team| team.phiquadro_sess)
+        .chain(contest.phiquadro_sess)
+        .collect();
+
+    phiquadro_sessions.sort_unstable();
+    phiquadro_sessions.dedup();
+
     let teams: Vec<Team> = teams
         .iter()
         .map(|team| Team {
             id: team.id,
             name: team.team_name.clone(),
             is_fake: team.is_fake,
+            phiquadro_sess: team.phiquadro_sess,
+            hidden: team.hidden,
+            is_bot: team.is_bot,
+            is_ghost: team.is_ghost,
             score: questions.len() as i64 * 10,
             questions: vec![TeamQuestion::default(); questions.len()],
         })
@@ -92,6 +120,7 @@ pub async fn fetch_contest(db: &mut Connection<DB>, user_id: i32, id: i32) -> an
         name: contest.contest_name.clone(),
         phiquadro_id: contest.phiquadro_id,
         phiquadro_sess: contest.phiquadro_sess,
+        phiquadro_sessions,
         duration: TimeDelta::seconds(contest.duration as i64),
         drift: contest.drift,
         start_time: contest.start_time,
@@ -101,29 +130,24 @@ pub async fn fetch_contest(db: &mut Connection<DB>, user_id: i32, id: i32) -> an
         jolly_time: TimeDelta::seconds(contest.jolly_time as i64),
         question_bonus: contest.question_bonus.iter().map(|&x| x.expect("Question bonus can't be null")).collect(),
         contest_bonus: contest.contest_bonus.iter().map(|&x| x.expect("Contest bonus can't be null")).collect(),
+        hidden_drift: contest.hidden_drift,
+        time_scale: contest.time_scale,
+        paused_at: contest.paused_at,
+        start_offset: TimeDelta::seconds(contest.start_offset as i64),
+        owner_id: contest.owner_id,
+        freeze_time: contest.freeze_time.map(|freeze_time| TimeDelta::seconds(freeze_time as i64)),
+        revealed: contest.revealed,
     }))
 }
 
-pub async fn fetch_contest_with_ranking(db: &mut Connection<DB>, user_id: i32, id: i32) -> anyhow::Result<Option<Contest>> {
+/// Loads the submissions (sorted by time) and the jollies of a contest which were sent until the given time
+pub async fn fetch_activity(
+    db: &mut Connection<DB>,
+    id: i32,
+    until: DateTime<Utc>,
+) -> anyhow::Result<(Vec<ContestSubmissions>, Vec<ContestJollies>)> {
     use crate::schema::{jollies, questions, submissions, teams};
 
-    let Some(mut contest) = fetch_contest(db, user_id, id).await? else {
-        return Ok(None);
-    };
-
-    let Contest {
-        questions,
-        teams,
-        drift,
-        drift_time,
-        start_time,
-        question_bonus,
-        contest_bonus,
-        ..
-    } = &mut contest;
-
-    let now = chrono::offset::Utc::now();
-
     let submissions = submissions::dsl::submissions
         .inner_join(questions::table)
         .inner_join(teams::table)
@@ -137,7 +161,7 @@ pub async fn fetch_contest_with_ranking(db: &mut Connection<DB>, user_id: i32, i
             teams::contest_id,
         ))
         .filter(teams::contest_id.eq(id))
-        .filter(submissions::sub_time.le(now))
+        .filter(submissions::sub_time.le(until))
         .order((submissions::sub_time.asc(), submissions::id.asc()))
         .load::<ContestSubmissions>(db)
         .await?;
@@ -152,90 +176,80 @@ pub async fn fetch_contest_with_ranking(db: &mut Connection<DB>, user_id: i32, i
             teams::contest_id,
         ))
         .filter(teams::contest_id.eq(id))
-        .filter(jollies::sub_time.le(now))
+        .filter(jollies::sub_time.le(until))
         .load::<ContestJollies>(db)
         .await?;
 
-    let drift_no = *drift;
-    let mut correct = vec![0; questions.len()];
-    let mut wrong = vec![vec![false; questions.len()]; teams.len()];
-    let mut drift = vec![*drift_time; questions.len()];
-
-    for submission in &submissions {
-        let q_pos = submission.question_pos as usize;
-        let t_pos = submission.team_pos as usize;
-        let sub_time = submission.sub_time - *start_time;
-
-        if submission.given_answer == submission.correct_answer {
-            correct[q_pos] += 1;
-            if correct[q_pos] >= drift_no {
-                drift[q_pos] = cmp::min(drift[q_pos], sub_time);
-            }
-        } else {
-            if sub_time < *drift_time && submission.given_answer != submission.correct_answer {
-                if correct[q_pos] == 0 && !wrong[t_pos][q_pos] {
-                    questions[q_pos].score += 2;
-                }
-                wrong[t_pos][q_pos] = true;
-            }
-        }
-    }
+    Ok((submissions, jollies))
+}
 
-    for i in 0..questions.len() {
-        questions[i].score += cmp::min(drift[i], now - *start_time).num_minutes().max(0);
-        if chrono::offset::Utc::now() >= *start_time + drift[i] {
-            questions[i].locked = true;
-        }
+/// The time the ranking of a contest is shown at. The clock of a paused contest is stopped, while before the user
+/// teams join the contest nothing is shown, even the activity replayed before the offset
+pub fn ranking_time(contest: &Contest) -> DateTime<Utc> {
+    let mut now = contest.paused_at.unwrap_or_else(chrono::offset::Utc::now);
+    if now < contest.join_time() {
+        now -= contest.join_time() - contest.start_time;
     }
 
-    let mut question_solves = vec![0; questions.len()];
-    let mut team_solves = vec![0; teams.len()];
-    let mut solves = 0;
-
-    for submission in &submissions {
-        let q_pos = submission.question_pos as usize;
-        let t_pos = submission.team_pos as usize;
-
-        if submission.given_answer == submission.correct_answer {
-            if
-                teams[t_pos].questions[q_pos].status != QuestionStatus::Solved
-                && teams[t_pos].questions[q_pos].status != QuestionStatus::JustSolved
-            {
-                teams[t_pos].questions[q_pos].score +=
-                    questions[q_pos].score + *question_bonus.get(question_solves[q_pos]).unwrap_or(&0) as i64;
-
-                question_solves[q_pos] += 1;
-                team_solves[t_pos] += 1;
-
-                if team_solves[t_pos] == questions.len() {
-                    teams[t_pos].score += *contest_bonus.get(solves).unwrap_or(&0) as i64;
-                    solves += 1;
-                }
-
-                teams[t_pos].questions[q_pos].status =if submission.sub_time >= now - TimeDelta::minutes(1) {
-                    QuestionStatus::JustSolved
-                } else {
-                    QuestionStatus::Solved
-                };
-            }
-        } else {
-            if teams[t_pos].questions[q_pos].status == QuestionStatus::NotAttempted {
-                teams[t_pos].questions[q_pos].status = QuestionStatus::Attempted;
-            }
-            teams[t_pos].questions[q_pos].score -= 10;
-        }
-    }
+    now
+}
 
-    for jolly in jollies {
-        teams[jolly.team_pos as usize].questions[jolly.question_pos as usize].score *= 2;
-        teams[jolly.team_pos as usize].questions[jolly.question_pos as usize].jolly = true;
+/// The real time after which the public ranking of a contest stops changing, unless it was revealed
+fn freeze_time(contest: &Contest) -> Option<DateTime<Utc>> {
+    contest.freeze_time
+        .filter(|_| !contest.revealed)
+        .map(|freeze_time| contest.real_time(freeze_time))
+}
+
+/// The time the public ranking of a contest is shown at, which stops at the freeze until the owner reveals it
+pub fn public_ranking_time(contest: &Contest) -> DateTime<Utc> {
+    let now = ranking_time(contest);
+
+    match freeze_time(contest) {
+        Some(freeze_time) => cmp::min(now, freeze_time),
+        None => now,
     }
+}
+
+/// Whether the public ranking of a contest is behind the one seen by its owner
+pub fn is_frozen(contest: &Contest) -> bool {
+    freeze_time(contest).is_some_and(|freeze_time| ranking_time(contest) > freeze_time)
+}
+
+/// Whether a contest is over, so that its ranking is the final one
+pub fn is_over(contest: &Contest) -> bool {
+    ranking_time(contest) >= contest.end_time()
+}
 
-    for team in teams.iter_mut() {
-        team.score += team.questions.iter().map(|q| q.score).sum::<i64>();
+/// Ranks the teams of a contest at the given time, reading the final ranking once the contest is over
+async fn rank_contest(db: &mut Connection<DB>, mut contest: Contest, now: DateTime<Utc>) -> anyhow::Result<Contest> {
+    if now >= contest.end_time() {
+        return fetch_final_ranking(db, contest).await;
     }
 
-    teams.sort_unstable_by_key(|team| -team.score);
+    let (submissions, jollies) = fetch_activity(db, contest.id, now).await?;
+
+    compute_ranking(&mut contest, &submissions, &jollies, now);
+    contest.teams.retain(|team| !team.hidden);
+
+    Ok(contest)
+}
+
+pub async fn fetch_contest_with_ranking(db: &mut Connection<DB>, user_id: i32, id: i32) -> anyhow::Result<Option<Contest>> {
+    let Some(contest) = fetch_contest(db, user_id, id).await? else {
+        return Ok(None);
+    };
+
+    let now = ranking_time(&contest);
+    Ok(Some(rank_contest(db, contest, now).await?))
+}
+
+/// Loads a contest with the ranking shown to the spectators, without checking who owns it
+pub async fn fetch_contest_with_public_ranking(db: &mut Connection<DB>, id: i32) -> anyhow::Result<Option<Contest>> {
+    let Some(contest) = fetch_contest_unchecked(db, id).await? else {
+        return Ok(None);
+    };
 
-    Ok(Some(contest))
+    let now = public_ranking_time(&contest);
+    Ok(Some(rank_contest(db, contest, now).await?))
 }
@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+
+use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+use diesel::{update, ExpressionMethods, QueryDsl};
+use rocket::http::Status;
+use rocket_db_pools::diesel::prelude::RunQueryDsl;
+use rocket_db_pools::Connection;
+use serde::Serialize;
+use tracing::info;
+
+use super::fetch::fetch_contest;
+use crate::model::{real_to_contest_time, Jolly, Submission, Team};
+use crate::error::{IntoStatusResult, Result};
+use crate::DB;
+
+/// A team of the users which can be copied into a contest as a ghost
+#[derive(Clone, Debug, Serialize)]
+pub struct GhostSource {
+    pub contest_id: i32,
+    pub contest_name: String,
+    pub team_id: i32,
+    pub team_name: String,
+}
+
+/// Loads the answers of the questions of every active contest of a user, in order
+async fn fetch_answers(db: &mut Connection<DB>, user_id: i32) -> anyhow::Result<HashMap<i32, Vec<i32>>> {
+    use crate::schema::{contests, questions};
+
+    let questions = questions::dsl::questions
+        .inner_join(contests::table)
+        .select((questions::contest_id, questions::answer))
+        .filter(contests::owner_id.eq(user_id))
+        .filter(contests::active.eq(true))
+        .order((questions::contest_id.asc(), questions::position.asc()))
+        .load::<(i32, i32)>(db)
+        .await?;
+
+    let mut answers: HashMap<i32, Vec<i32>> = HashMap::new();
+    for (contest_id, answer) in questions {
+        answers.entry(contest_id).or_default().push(answer);
+    }
+
+    Ok(answers)
+}
+
+/// Lists the teams of the users, in the other contests of the same user with the same questions, which can be copied
+/// into a contest
+pub async fn fetch_ghost_sources(db: &mut Connection<DB>, user_id: i32, id: i32) -> anyhow::Result<Vec<GhostSource>> {
+    use crate::schema::{contests, teams};
+
+    let answers = fetch_answers(db, user_id).await?;
+    let Some(target) = answers.get(&id) else {
+        return Ok(vec![]);
+    };
+
+    let compatible: Vec<i32> = answers
+        .iter()
+        .filter(|&(&contest_id, answers)| contest_id != id && answers == target)
+        .map(|(&contest_id, _)| contest_id)
+        .collect();
+
+    let sources = teams::dsl::teams
+        .inner_join(contests::table)
+        .select((contests::id, contests::contest_name, teams::id, teams::team_name))
+        .filter(teams::contest_id.eq_any(compatible))
+        .filter(teams::is_fake.eq(false))
+        .order((contests::start_time.desc(), teams::position.asc()))
+        .load::<(i32, String, i32, String)>(db)
+        .await?;
+
+    Ok(sources
+        .into_iter()
+        .map(|(contest_id, contest_name, team_id, team_name)| GhostSource {
+            contest_id,
+            contest_name,
+            team_id,
+            team_name,
+        })
+        .collect())
+}
+
+/// Copies a team of the users from another contest of the same user, with the same questions, into a contest which
+/// has not started yet as a fake team. The times of its submissions and jolly are kept relative to the start of the
+/// contest, so they follow the speed of each contest. Returns the id of the new team
+pub async fn copy_ghost(
+    db: &mut Connection<DB>,
+    user_id: i32,
+    id: i32,
+    source_id: i32,
+    source_team_id: i32,
+    name: Option<&str>,
+) -> Result<i32> {
+    use crate::schema::{contests, jollies, questions, submissions, teams};
+
+    let Some(contest) = fetch_contest(db, user_id, id)
+        .await
+        .attach_info(Status::InternalServerError, "Errore incontrato durante la copia della squadra")?
+    else {
+        return Err(anyhow!("Contest {} not found for user {}", id, user_id))
+            .attach_info(Status::NotFound, "La gara non esiste o non ti appartiene");
+    };
+
+    let source = match fetch_contest(db, user_id, source_id)
+        .await
+        .attach_info(Status::InternalServerError, "Errore incontrato durante la copia della squadra")?
+    {
+        Some(source) if source_id != id => source,
+        _ => {
+            return Err(anyhow!("Source contest {} not found for user {}", source_id, user_id))
+                .attach_info(Status::NotFound, "La gara di origine non esiste o non ti appartiene");
+        }
+    };
+
+    let Some(source_team) = source.teams.iter().find(|team| team.id == source_team_id && !team.is_fake) else {
+        return Err(anyhow!("Team {} is not a user team of contest {}", source_team_id, source_id))
+            .attach_info(Status::NotFound, "La squadra non esiste o non è stata aggiunta da te");
+    };
+
+    if contest.join_time() <= Utc::now() {
+        return Err(anyhow!("Contest {} has already started", id))
+            .attach_info(Status::Forbidden, "La gara è già iniziata");
+    }
+
+    let same_questions = contest.questions.len() == source.questions.len()
+        && contest.questions.iter().zip(&source.questions).all(|(a, b)| a.answer == b.answer);
+    if !same_questions {
+        return Err(anyhow!("Contests {} and {} have different questions", id, source_id))
+            .attach_info(Status::UnprocessableEntity, "Le due gare non hanno gli stessi quesiti");
+    }
+
+    info!("Copying team {} of contest {} into contest {}", source_team_id, source_id, id);
+
+    let source_submissions = submissions::dsl::submissions
+        .inner_join(questions::table)
+        .select((submissions::answer, submissions::sub_time, questions::position))
+        .filter(submissions::team_id.eq(source_team_id))
+        .order((submissions::sub_time.asc(), submissions::id.asc()))
+        .load::<(i32, DateTime<Utc>, i32)>(db)
+        .await
+        .attach_info(Status::InternalServerError, "Errore incontrato durante la copia della squadra")?;
+
+    let source_jollies = jollies::dsl::jollies
+        .inner_join(questions::table)
+        .select((jollies::sub_time, questions::position))
+        .filter(jollies::team_id.eq(source_team_id))
+        .load::<(DateTime<Utc>, i32)>(db)
+        .await
+        .attach_info(Status::InternalServerError, "Errore incontrato durante la copia della squadra")?;
+
+    // The same instant of contest time in the two contests
+    let copy_time = |sub_time: DateTime<Utc>| {
+        contest.real_time(real_to_contest_time(sub_time - source.start_time, source.time_scale))
+    };
+
+    let team_name = match name {
+        Some(name) if !name.trim().is_empty() => name.trim().to_string(),
+        _ => format!("{} ({})", source_team.name, source.name),
+    };
+
+    let team_id = diesel::insert_into(teams::table)
+        .values(Team {
+            team_name,
+            is_fake: true,
+            position: contest.teams.len() as i32,
+            contest_id: id,
+            phiquadro_sess: None,
+            hidden: false,
+            is_bot: false,
+            is_ghost: true,
+        })
+        .returning(teams::id)
+        .get_result::<i32>(db)
+        .await
+        .attach_info(Status::InternalServerError, "Errore incontrato durante la copia della squadra")?;
+
+    diesel::insert_into(submissions::table)
+        .values(
+            source_submissions
+                .iter()
+                .map(|&(answer, sub_time, question)| Submission {
+                    answer,
+                    sub_time: copy_time(sub_time),
+                    team_id,
+                    question_id: contest.questions[question as usize].id,
+                })
+                .collect::<Vec<_>>(),
+        )
+        .execute(db)
+        .await
+        .attach_info(Status::InternalServerError, "Errore incontrato durante la copia della squadra")?;
+
+    diesel::insert_into(jollies::table)
+        .values(
+            source_jollies
+                .iter()
+                .map(|&(sub_time, question)| Jolly {
+                    sub_time: copy_time(sub_time),
+                    team_id,
+                    question_id: contest.questions[question as usize].id,
+                })
+                .collect::<Vec<_>>(),
+        )
+        .execute(db)
+        .await
+        .attach_info(Status::InternalServerError, "Errore incontrato durante la copia della squadra")?;
+
+    update(contests::dsl::contests.filter(contests::id.eq(id)))
+        .set(contests::teams_no.eq(contests::teams_no + 1))
+        .execute(db)
+        .await
+        .attach_info(Status::InternalServerError, "Errore incontrato durante la copia della squadra")?;
+
+    Ok(team_id)
+}
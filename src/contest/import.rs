@@ -1,3 +1,4 @@
+use std::collections::HashSet;
 use std::io::Write;
 use std::process::{Command, Stdio};
 use std::str::from_utf8;
@@ -20,7 +21,9 @@ use rocket_db_pools::Connection;
 
 use scraper::{Html, Selector};
 
-use crate::model::{Contest, Jolly, Question, Submission, Team};
+use super::interchange::ContestDocument;
+use super::selection::{apply_team_selection, TeamSelection};
+use crate::model::{contest_to_real_time, Contest, Jolly, Question, Submission, Team};
 use crate::{PhiQuadroLogin, DB};
 
 use crate::error::{IntoStatusResult, Result};
@@ -30,10 +33,14 @@ const CONTEST_STATS_URL: &str = "https://www.phiquadro.it/gara_a_squadre/stampe/
 const TEAM_STATS_URL: &str = "https://www.phiquadro.it/gara_a_squadre/stampe/statistiche_squadra.php";
 const CONTESTS_URL: &str = "https://www.phiquadro.it/gara_a_squadre/insegnanti_gestione_statistiche.php";
 
+/// The slowest and the fastest speeds a contest can be replayed at
+pub const MIN_TIME_SCALE: f64 = 0.25;
+pub const MAX_TIME_SCALE: f64 = 10.0;
+
 #[derive(Clone, Debug)]
-struct TeamActivity {
-    submissions: Vec<(i64, i32, usize)>,
-    jolly: Option<usize>,
+pub struct TeamActivity {
+    pub submissions: Vec<(i64, i32, usize)>,
+    pub jolly: Option<usize>,
 }
 
 #[derive(Clone, Debug)]
@@ -42,37 +49,263 @@ struct ContestInfo {
     teams: Vec<(i32, String)>,
 }
 
-/// Fetches the data of a contest from phiquadro.it and inserts is into the database
+/// A team of a contest to be created, along with its activity
+#[derive(Clone, Debug)]
+pub struct TeamData {
+    pub name: String,
+    pub session: Option<i32>,
+    pub activity: TeamActivity,
+}
+
+/// The data of a contest to be created, independent from its source
+#[derive(Clone, Debug)]
+pub struct ContestData {
+    pub name: String,
+    pub answers: Vec<i32>,
+    pub teams: Vec<TeamData>,
+}
+
+impl ContestData {
+    /// Checks that every submission and jolly refers to an existing question
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.answers.is_empty() {
+            bail!("A contest needs at least one question");
+        }
+
+        for team in &self.teams {
+            let questions = team
+                .activity
+                .submissions
+                .iter()
+                .map(|&(_, _, question)| question)
+                .chain(team.activity.jolly);
+
+            for question in questions {
+                if question >= self.answers.len() {
+                    bail!("Team {} refers to question {}, which does not exist", team.name, question + 1);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks that every submission is given during a contest lasting `duration` seconds
+    pub fn validate_times(&self, duration: u32) -> anyhow::Result<()> {
+        for team in &self.teams {
+            for &(minute, _, question) in &team.activity.submissions {
+                if minute < 0 || minute * 60 >= duration as i64 {
+                    bail!(
+                        "Team {} answers question {} at minute {}, outside the contest",
+                        team.name,
+                        question + 1,
+                        minute,
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The settings chosen by the user for a contest to be created
+#[derive(Clone, Debug)]
+pub struct ContestSettings<'r> {
+    pub owner_id: i32,
+    pub name: &'r str,
+    pub duration: u32,
+    pub start_time: DateTime<Utc>,
+    pub start_offset: u32,
+    pub drift: u32,
+    pub drift_time: u32,
+    pub jolly_time: u32,
+    pub question_bonus: [i32; 10],
+    pub contest_bonus: [i32; 10],
+    pub time_scale: f64,
+    pub team_selection: Option<TeamSelection>,
+    pub hidden_drift: bool,
+    pub freeze_time: Option<u32>,
+}
+
+/// Fetches the data of a contest from phiquadro.it, joining the teams of all the given sessions, and inserts it into
+/// the database
 pub async fn create_contest(
     db: &mut Connection<DB>,
     phi: &PhiQuadroLogin,
-    owner_id: i32,
-    name: &str,
     id: u32,
-    sess: u32,
-    duration: u32,
-    start_time: DateTime<Utc>,
-    drift: u32,
-    drift_time: u32,
-    jolly_time: u32,
-    question_bonus: [i32; 10],
-    contest_bonus: [i32; 10],
+    sessions: &[u32],
+    settings: ContestSettings<'_>,
 ) -> Result<i32> {
-    use crate::schema::{contests, jollies, questions, submissions, teams};
-
-    info!("Adding contest {}/{}", id, sess);
-
-    // Sanity checks of the values to insert
+    info!("Adding contest {}/{:?}", id, sessions);
 
     let id = id
         .try_into()
         .map_err(|_| anyhow!("PhiQuadro ID should be a reasonable value ({} given)", id))
         .attach_info(Status::UnprocessableEntity, "ID PhiQuadro non valido")?;
 
-    let sess = sess
-        .try_into()
-        .map_err(|_| anyhow!("PhiQuadro session should be a reasonable value ({} given)", sess))
-        .attach_info(Status::UnprocessableEntity, "Sessione PhiQuadro non valida")?;
+    let mut sessions = sessions
+        .iter()
+        .map(|&sess| sess
+            .try_into()
+            .map_err(|_| anyhow!("PhiQuadro session should be a reasonable value ({} given)", sess))
+            .attach_info(Status::UnprocessableEntity, "Sessione PhiQuadro non valida")
+        )
+        .collect::<Result<Vec<i32>>>()?;
+
+    let mut seen = HashSet::new();
+    sessions.retain(|&sess| seen.insert(sess));
+
+    let Some(&first_sess) = sessions.get(0) else {
+        return Err(anyhow!("No PhiQuadro session was given"))
+            .attach_info(Status::UnprocessableEntity, "Indica almeno una sessione PhiQuadro");
+    };
+
+    // Setting up a phiquadro client
+    let mut client = get_phiquadro_client(phi)
+        .await
+        .context("While initializing PhiQuadro HTTP client")
+        .attach_info(Status::ServiceUnavailable, "Non riesco a contattare PhiQuadro")?;
+
+    let mut name = None;
+    let mut answers: Option<Vec<i32>> = None;
+    let mut teams = vec![];
+
+    for &sess in &sessions {
+        // Fetching the teams from phiquadro
+        let contest_info = get_contest_info(&mut client, id, sess)
+            .await
+            .context("While fetching teams for given contest")
+            .attach_info(Status::ServiceUnavailable, "Non riesco a importare la gara")?;
+
+        // Fetching the questions from phiquadro, which have to be the same in every session
+        let sess_answers = get_questions(&mut client, id, sess).await?;
+        info!("Answers of session {} are {:?}", sess, sess_answers);
+
+        match &answers {
+            Some(answers) if *answers != sess_answers => {
+                return Err(anyhow!("Session {} has different answers from session {}", sess, first_sess))
+                    .attach_info(Status::UnprocessableEntity, "Le sessioni indicate hanno risposte diverse");
+            }
+            Some(_) => {}
+            None => answers = Some(sess_answers),
+        }
+
+        name.get_or_insert(contest_info.name);
+
+        for (team_id, team_name) in contest_info.teams {
+            info!("Fetching {team_id} {team_name}");
+            let activity = get_submissions(&mut client, id, sess, team_id).await?;
+            teams.push(TeamData { name: team_name, session: Some(sess), activity });
+        }
+    }
+
+    let data = ContestData {
+        name: name.unwrap_or_default(),
+        answers: answers.unwrap_or_default(),
+        teams,
+    };
+
+    data.validate()
+        .attach_info(Status::InternalServerError, "Errore incontrato durante l'importazione della gara")?;
+
+    insert_contest(db, Some((id, first_sess)), settings, &data).await
+}
+
+/// Inserts a contest described in the interchange format into the database
+pub async fn import_contest(
+    db: &mut Connection<DB>,
+    document: ContestDocument,
+    settings: ContestSettings<'_>,
+) -> Result<i32> {
+    let data = document
+        .into_contest_data()
+        .context("While parsing the contest document")
+        .attach_info(Status::UnprocessableEntity, "Il documento della gara non è valido")?;
+
+    data.validate_times(settings.duration)
+        .attach_info(Status::UnprocessableEntity, "Il documento contiene risposte date fuori dalla durata della gara")?;
+
+    info!("Importing contest {}", data.name);
+
+    insert_contest(db, None, settings, &data).await
+}
+
+/// Inserts a contest whose questions and ghost teams were entered by hand into the database
+pub async fn create_custom_contest(
+    db: &mut Connection<DB>,
+    answers: Vec<i32>,
+    ghost_teams: Vec<TeamData>,
+    settings: ContestSettings<'_>,
+) -> Result<i32> {
+    if settings.name.is_empty() {
+        return Err(anyhow!("Custom contests need a name"))
+            .attach_info(Status::UnprocessableEntity, "Inserisci il nome della simulazione");
+    }
+
+    let data = ContestData {
+        name: settings.name.to_string(),
+        answers,
+        teams: ghost_teams,
+    };
+
+    data.validate()
+        .attach_info(Status::UnprocessableEntity, "Assicurati che le risposte delle squadre fantasma si riferiscano a quesiti esistenti")?;
+
+    data.validate_times(settings.duration)
+        .attach_info(Status::UnprocessableEntity, "Assicurati che le squadre fantasma rispondano durante la gara")?;
+
+    info!("Creating custom contest {}", data.name);
+
+    insert_contest(db, None, settings, &data).await
+}
+
+/// Inserts a contest, its questions and its fake teams into the database
+async fn insert_contest(
+    db: &mut Connection<DB>,
+    phiquadro: Option<(i32, i32)>,
+    settings: ContestSettings<'_>,
+    data: &ContestData,
+) -> Result<i32> {
+    use crate::schema::{contests, questions, teams};
+
+    let ContestSettings {
+        owner_id,
+        name,
+        duration,
+        start_time,
+        start_offset,
+        drift,
+        drift_time,
+        jolly_time,
+        question_bonus,
+        contest_bonus,
+        time_scale,
+        team_selection,
+        hidden_drift,
+        freeze_time,
+    } = settings;
+
+    // Sanity checks of the values to insert
+
+    if start_offset >= duration {
+        return Err(anyhow!("Start offset should be before the end of the contest ({} given)", start_offset))
+            .attach_info(Status::UnprocessableEntity, "Il minuto di partenza deve precedere la fine della gara");
+    }
+
+    if freeze_time.is_some_and(|freeze_time| freeze_time >= duration) {
+        return Err(anyhow!("Freeze time should be before the end of the contest ({:?} given)", freeze_time))
+            .attach_info(
+                Status::UnprocessableEntity,
+                "Il congelamento della classifica deve precedere la fine della gara",
+            );
+    }
+
+    // The selection is checked before inserting anything, so that a wrong one doesn't leave a contest behind
+    if let Some(team_selection) = &team_selection {
+        team_selection.validate()?;
+        team_selection.check_teams(data.teams.iter().map(|team| team.name.as_str()))?;
+    }
 
     let drift = drift
         .try_into()
@@ -94,6 +327,16 @@ pub async fn create_contest(
         .map_err(|_| anyhow!("Jolly time should be a reasonable value ({} given)", jolly_time))
         .attach_info(Status::UnprocessableEntity, "Durata scelta jolly non valida")?;
 
+    if !(MIN_TIME_SCALE..=MAX_TIME_SCALE).contains(&time_scale) {
+        return Err(anyhow!("Time scale should be a reasonable value ({} given)", time_scale))
+            .attach_info(Status::UnprocessableEntity, "Velocità di riproduzione non valida");
+    }
+
+    // The replay starts earlier than the time chosen by the user, when the user teams join the contest
+    let start_time = start_time - contest_to_real_time(TimeDelta::seconds(start_offset as i64), time_scale);
+    let start_offset = start_offset as i32;
+    let freeze_time = freeze_time.map(|freeze_time| freeze_time as i32);
+
     let question_bonus = question_bonus
         .into_iter()
         .map(Some)
@@ -104,42 +347,31 @@ pub async fn create_contest(
         .map(Some)
         .collect::<Vec<_>>();
 
-    // Setting up a phiquadro client
-    let mut client = get_phiquadro_client(phi)
-        .await
-        .context("While initializing PhiQuadro HTTP client")
-        .attach_info(Status::ServiceUnavailable, "Non riesco a contattare PhiQuadro")?;
-
-    // Fetching the teams from phiquadro
-    let contest_info = get_contest_info(&mut client, id, sess)
-        .await
-        .context("While fetching teams for given contest")
-        .attach_info(Status::ServiceUnavailable, "Non riesco a importare la gara")?;
-
-    let teams = contest_info.teams;
-    let name = if name.is_empty() { &contest_info.name } else { name };
-
-    // Fetching the questions from phiquadro
-    let answers = get_questions(&mut client, id, sess).await?;
-    info!("Answers are {:?}", answers);
+    let name = if name.is_empty() { &data.name } else { name };
 
     // Inserting the new contest
     let contest_id = diesel::insert_into(contests::table)
         .values(&Contest {
             contest_name: name.to_string(),
-            phiquadro_id: id,
-            phiquadro_sess: sess,
+            phiquadro_id: phiquadro.map(|(id, _)| id),
+            phiquadro_sess: phiquadro.map(|(_, sess)| sess),
             duration,
             start_time,
             drift,
             drift_time,
             jolly_time,
-            teams_no: teams.len() as i32,
-            questions_no: answers.len() as i32,
+            teams_no: data.teams.len() as i32,
+            questions_no: data.answers.len() as i32,
             active: false,
             question_bonus,
             contest_bonus,
-            owner_id: owner_id,
+            owner_id,
+            hidden_drift,
+            time_scale,
+            paused_at: None,
+            start_offset,
+            freeze_time,
+            revealed: false,
         })
         .returning(contests::id)
         .get_result(db)
@@ -149,25 +381,29 @@ pub async fn create_contest(
     // Inserting the teams into the database
     let teams_id = diesel::insert_into(teams::table)
         .values(
-            teams
+            data.teams
                 .iter()
                 .enumerate()
-                .map(|(i, (_team_id, team_name))| Team {
-                    team_name: team_name.clone(),
+                .map(|(i, team)| Team {
+                    team_name: team.name.clone(),
                     is_fake: true,
                     position: i as i32,
                     contest_id,
+                    phiquadro_sess: team.session,
+                    hidden: false,
+                    is_bot: false,
+                    is_ghost: false,
                 })
                 .collect::<Vec<_>>(),
         )
         .returning(teams::id)
-        .get_results(db)
+        .get_results::<i32>(db)
         .await
         .attach_info(Status::InternalServerError, "Errore incontrato durante l'importazione della gara")?;
 
     let questions = diesel::insert_into(questions::table)
         .values(
-            answers
+            data.answers
                 .iter()
                 .enumerate()
                 .map(|(i, &answer)| Question {
@@ -178,42 +414,16 @@ pub async fn create_contest(
                 .collect::<Vec<_>>(),
         )
         .returning(questions::id)
-        .get_results(db)
+        .get_results::<i32>(db)
         .await
         .attach_info(Status::InternalServerError, "Errore incontrato durante l'importazione della gara")?;
 
-    for (i, (team_id, team_name)) in teams.iter().enumerate() {
-        info!("Inserting {team_id} {team_name}");
-        let submissions = get_submissions(&mut client, id, sess, *team_id).await?;
-
-        diesel::insert_into(submissions::table)
-            .values(
-                submissions
-                    .submissions
-                    .iter()
-                    .map(|&(sub_time, answer, question)| Submission {
-                        question_id: questions[question],
-                        team_id: teams_id[i],
-                        sub_time: start_time + TimeDelta::minutes(sub_time),
-                        answer,
-                    })
-                    .collect::<Vec<_>>(),
-            )
-            .execute(db)
+    for (team, &team_id) in data.teams.iter().zip(&teams_id) {
+        info!("Inserting {team_id} {}", team.name);
+
+        insert_team_activity(db, team_id, &team.activity, &questions, start_time, time_scale)
             .await
             .attach_info(Status::InternalServerError, "Errore incontrato durante l'importazione della gara")?;
-
-        if let Some(jolly) = submissions.jolly {
-            diesel::insert_into(jollies::table)
-                .values(&Jolly {
-                    question_id: questions[jolly],
-                    sub_time: start_time + TimeDelta::minutes(10),
-                    team_id: teams_id[i],
-                })
-                .execute(db)
-                .await
-                .attach_info(Status::InternalServerError, "Errore incontrato durante l'importazione della gara")?;
-        }
     }
 
     update(contests::dsl::contests.filter(contests::id.eq(contest_id)))
@@ -222,6 +432,10 @@ pub async fn create_contest(
         .await
         .attach_info(Status::InternalServerError, "Errore incontrato durante l'importazione della gara")?;
 
+    if let Some(team_selection) = team_selection {
+        apply_team_selection(db, owner_id, contest_id, &team_selection, hidden_drift).await?;
+    }
+
     Ok(contest_id)
 }
 
@@ -397,3 +611,44 @@ fn parse_team_text(text: &[u8]) -> anyhow::Result<TeamActivity> {
 
     Ok(TeamActivity { submissions, jolly })
 }
+
+/// Inserts the submissions and the jolly of a fake team, given the ids of the questions of its contest
+pub async fn insert_team_activity(
+    db: &mut Connection<DB>,
+    team_id: i32,
+    activity: &TeamActivity,
+    questions: &[i32],
+    start_time: DateTime<Utc>,
+    time_scale: f64,
+) -> anyhow::Result<()> {
+    use crate::schema::{jollies, submissions};
+
+    diesel::insert_into(submissions::table)
+        .values(
+            activity
+                .submissions
+                .iter()
+                .map(|&(sub_time, answer, question)| Submission {
+                    question_id: questions[question],
+                    team_id,
+                    sub_time: start_time + contest_to_real_time(TimeDelta::minutes(sub_time), time_scale),
+                    answer,
+                })
+                .collect::<Vec<_>>(),
+        )
+        .execute(db)
+        .await?;
+
+    if let Some(jolly) = activity.jolly {
+        diesel::insert_into(jollies::table)
+            .values(&Jolly {
+                question_id: questions[jolly],
+                sub_time: start_time + contest_to_real_time(TimeDelta::minutes(10), time_scale),
+                team_id,
+            })
+            .execute(db)
+            .await?;
+    }
+
+    Ok(())
+}
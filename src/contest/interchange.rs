@@ -0,0 +1,287 @@
+use anyhow::{anyhow, bail, ensure, Context};
+use serde::Deserialize;
+
+use super::import::{ContestData, TeamActivity, TeamData};
+
+/// The latest version of the interchange format
+pub const DOCUMENT_VERSION: u32 = 1;
+
+/// A contest described in the interchange format, either as a JSON object or as CSV text
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum ContestDocument {
+    Json(JsonDocument),
+    Csv(String),
+}
+
+#[derive(Deserialize)]
+pub struct JsonDocument {
+    version: u32,
+    name: String,
+    questions: Vec<i32>,
+    #[serde(default)]
+    teams: Vec<JsonTeam>,
+}
+
+/// A team of the document, which is also how ghost teams are described when creating a contest by hand
+#[derive(Deserialize)]
+pub struct JsonTeam {
+    name: String,
+    #[serde(default)]
+    submissions: Vec<JsonSubmission>,
+    jolly: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct JsonSubmission {
+    minute: i64,
+    question: usize,
+    answer: i32,
+}
+
+impl ContestDocument {
+    /// Converts the document into the data needed to create a contest
+    pub fn into_contest_data(self) -> anyhow::Result<ContestData> {
+        let data = match self {
+            ContestDocument::Json(document) => document.into_contest_data()?,
+            ContestDocument::Csv(text) => parse_csv_document(&text)?,
+        };
+
+        data.validate()?;
+        Ok(data)
+    }
+}
+
+impl JsonDocument {
+    fn into_contest_data(self) -> anyhow::Result<ContestData> {
+        ensure!(self.version == DOCUMENT_VERSION, "Unsupported document version {}", self.version);
+
+        let teams = self
+            .teams
+            .into_iter()
+            .map(|team| team.into_team_data())
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(ContestData {
+            name: self.name,
+            answers: self.questions,
+            teams,
+        })
+    }
+}
+
+impl JsonTeam {
+    /// Converts a team of the document, whose questions are numbered from 1
+    pub fn into_team_data(self) -> anyhow::Result<TeamData> {
+        let submissions = self
+            .submissions
+            .iter()
+            .map(|sub| Ok((sub.minute, sub.answer, question_index(sub.question)?)))
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(TeamData {
+            name: self.name,
+            session: None,
+            activity: TeamActivity {
+                submissions,
+                jolly: self.jolly.map(question_index).transpose()?,
+            },
+        })
+    }
+}
+
+fn question_index(question: usize) -> anyhow::Result<usize> {
+    question
+        .checked_sub(1)
+        .ok_or_else(|| anyhow!("Questions are numbered starting from 1"))
+}
+
+/// Parses a CSV document, made of one record per line whose first field is the type of the record:
+///
+/// ```text
+/// version,1
+/// name,<contest name>
+/// question,<question number>,<answer>
+/// team,<team label>,<team name>
+/// submission,<team label>,<minute>,<question number>,<answer>
+/// jolly,<team label>,<question number>
+/// ```
+fn parse_csv_document(text: &str) -> anyhow::Result<ContestData> {
+    let mut version = None;
+    let mut name = String::new();
+    let mut answers = vec![];
+    let mut labels = vec![];
+    let mut teams: Vec<TeamData> = vec![];
+
+    for (i, line) in text.lines().enumerate() {
+        let line_no = i + 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let fields = split_csv_line(line).with_context(|| format!("On line {line_no}"))?;
+        let field = |n: usize| {
+            fields
+                .get(n)
+                .map(|field| field.trim())
+                .ok_or_else(|| anyhow!("Missing field {} on line {}", n + 1, line_no))
+        };
+        let team = |label: &str| {
+            labels
+                .iter()
+                .position(|other| other == label)
+                .ok_or_else(|| anyhow!("Unknown team {} on line {}", label, line_no))
+        };
+
+        match field(0)? {
+            "version" => version = Some(field(1)?.parse::<u32>()?),
+            "name" => name = field(1)?.to_string(),
+            "question" => {
+                let position = field(1)?.parse::<usize>()?;
+                ensure!(position == answers.len() + 1, "Questions must be listed in order (line {line_no})");
+                answers.push(field(2)?.parse()?);
+            }
+            "team" => {
+                let label = field(1)?.to_string();
+                ensure!(!labels.contains(&label), "Team {label} is defined twice (line {line_no})");
+                labels.push(label);
+                teams.push(TeamData {
+                    name: field(2)?.to_string(),
+                    session: None,
+                    activity: TeamActivity { submissions: vec![], jolly: None },
+                });
+            }
+            "submission" => {
+                let team = team(field(1)?)?;
+                let minute = field(2)?.parse()?;
+                let question = question_index(field(3)?.parse()?)?;
+                let answer = field(4)?.parse()?;
+                teams[team].activity.submissions.push((minute, answer, question));
+            }
+            "jolly" => {
+                let team = team(field(1)?)?;
+                teams[team].activity.jolly = Some(question_index(field(2)?.parse()?)?);
+            }
+            record => bail!("Unknown record type {} on line {}", record, line_no),
+        }
+    }
+
+    match version {
+        Some(DOCUMENT_VERSION) => {}
+        Some(version) => bail!("Unsupported document version {}", version),
+        None => bail!("The document does not declare its version"),
+    }
+
+    Ok(ContestData { name, answers, teams })
+}
+
+/// Splits a line of CSV into fields, handling double quoted fields
+fn split_csv_line(line: &str) -> anyhow::Result<Vec<String>> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut chars = line.chars().peekable();
+    let mut quoted = false;
+
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            ('"', true) => quoted = false,
+            ('"', false) if field.trim().is_empty() => {
+                field.clear();
+                quoted = true;
+            }
+            (',', false) => fields.push(std::mem::take(&mut field)),
+            (c, _) => field.push(c),
+        }
+    }
+
+    ensure!(!quoted, "Unterminated quoted field");
+    fields.push(field);
+
+    Ok(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> anyhow::Result<ContestData> {
+        ContestDocument::Csv(text.to_string()).into_contest_data()
+    }
+
+    #[test]
+    fn split_quoted_fields() {
+        assert_eq!(split_csv_line("a, b ,,c").unwrap(), ["a", " b ", "", "c"]);
+        assert_eq!(
+            split_csv_line(r#"team,A,"Liceo ""Galilei"", Roma""#).unwrap(),
+            ["team", "A", r#"Liceo "Galilei", Roma"#],
+        );
+        assert_eq!(split_csv_line(r#" "a,b" ,c"#).unwrap(), ["a,b ", "c"]);
+        assert!(split_csv_line(r#"team,A,"Liceo"#).is_err());
+    }
+
+    #[test]
+    fn parse_document() {
+        let text = "version,1\r\nname,\"Gara, a squadre\"\r\n\r\nquestion,1,1234\r\nquestion,2,42\r\n\
+            team,A,\"Squadra \"\"A\"\"\"\r\nteam,B,Squadra B\r\nsubmission,A,12,2,42\r\njolly,A,1\r\n";
+        let data = parse(text).unwrap();
+
+        assert_eq!(data.name, "Gara, a squadre");
+        assert_eq!(data.answers, [1234, 42]);
+        assert_eq!(data.teams.len(), 2);
+        assert_eq!(data.teams[0].name, "Squadra \"A\"");
+        assert_eq!(data.teams[0].activity.submissions, [(12, 42, 1)]);
+        assert_eq!(data.teams[0].activity.jolly, Some(0));
+        assert_eq!(data.teams[1].name, "Squadra B");
+        assert!(data.teams[1].activity.submissions.is_empty());
+        assert_eq!(data.teams[1].activity.jolly, None);
+    }
+
+    #[test]
+    fn reject_bad_header() {
+        assert!(parse("name,Gara\nquestion,1,1").is_err());
+        assert!(parse("version,2\nquestion,1,1").is_err());
+        assert!(parse("version,uno\nquestion,1,1").is_err());
+        assert!(parse("versione,1\nquestion,1,1").is_err());
+        assert!(parse("version,1\nquestion,2,1").is_err());
+        assert!(parse("version,1").is_err());
+
+        let json = r#"{"version": 2, "name": "Gara", "questions": [1]}"#;
+        let document: ContestDocument = rocket::serde::json::from_str(json).unwrap();
+        assert!(document.into_contest_data().is_err());
+    }
+
+    #[test]
+    fn reject_bad_answers() {
+        assert!(parse("version,1\nquestion,1,abc").is_err());
+        assert!(parse("version,1\nquestion,1,99999999999").is_err());
+        assert!(parse("version,1\nquestion,1,1\nteam,A,A\nsubmission,A,3,1,99999999999").is_err());
+        assert!(parse("version,1\nquestion,1,1\nteam,A,A\nsubmission,A,3,1").is_err());
+    }
+
+    #[test]
+    fn reject_unknown_references() {
+        let header = "version,1\nquestion,1,1\nquestion,2,2\nteam,A,A\n";
+        assert!(parse(&format!("{header}submission,A,3,2,2")).is_ok());
+        assert!(parse(&format!("{header}submission,B,3,2,2")).is_err());
+        assert!(parse(&format!("{header}submission,A,3,3,2")).is_err());
+        assert!(parse(&format!("{header}submission,A,3,0,2")).is_err());
+        assert!(parse(&format!("{header}jolly,B,1")).is_err());
+        assert!(parse(&format!("{header}jolly,A,3")).is_err());
+        assert!(parse(&format!("{header}team,A,Doppia")).is_err());
+        assert!(parse(&format!("{header}risposta,A,3,2,2")).is_err());
+    }
+
+    #[test]
+    fn reject_submissions_outside_the_contest() {
+        let data = parse("version,1\nquestion,1,1\nteam,A,A\nsubmission,A,0,1,1\nsubmission,A,119,1,1").unwrap();
+        assert!(data.validate_times(120 * 60).is_ok());
+        assert!(data.validate_times(119 * 60).is_err());
+
+        let data = parse("version,1\nquestion,1,1\nteam,A,A\nsubmission,A,-1,1,1").unwrap();
+        assert!(data.validate_times(120 * 60).is_err());
+    }
+}
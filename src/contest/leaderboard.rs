@@ -0,0 +1,236 @@
+use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+use diesel::prelude::Queryable;
+use diesel::{update, BoolExpressionMethods, ExpressionMethods, QueryDsl};
+use rocket::http::Status;
+use rocket_db_pools::diesel::prelude::RunQueryDsl;
+use rocket_db_pools::Connection;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use super::contest::Contest;
+use super::fetch::{fetch_contest, fetch_contest_unchecked, is_over};
+use super::snapshot::fetch_final_ranking;
+use crate::error::{IntoStatusResult, Result};
+use crate::DB;
+
+/// Whether a simulation takes part in the leaderboard shared by the simulations of the same PhiQuadro contest, and
+/// what it shows there
+#[derive(Clone, Copy, Debug, Queryable, Serialize, Deserialize)]
+pub struct LeaderboardSettings {
+    pub opt_in: bool,
+    #[serde(default)]
+    pub hide_teams: bool,
+    #[serde(default)]
+    pub hide_owner: bool,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct LeaderboardEntry {
+    /// Starting from 1, shared by the teams with the same score
+    pub rank: usize,
+    /// `None` if the owner of the simulation chose to hide it
+    pub team_name: Option<String>,
+    /// `None` if the owner of the simulation chose to hide it
+    pub owner: Option<String>,
+    pub score: i64,
+    /// The rank of the team in its own simulation, fake teams included
+    pub simulation_rank: usize,
+    pub start_time: DateTime<Utc>,
+    /// Whether the team belongs to the user asking for the leaderboard
+    pub own: bool,
+}
+
+/// The final scores of the teams of the users in the ended simulations of the same PhiQuadro contest with the same
+/// scoring settings and teams
+#[derive(Clone, Debug, Serialize)]
+pub struct Leaderboard {
+    pub phiquadro_id: i32,
+    pub phiquadro_sess: Option<i32>,
+    pub simulations: usize,
+    pub entries: Vec<LeaderboardEntry>,
+}
+
+pub async fn fetch_leaderboard_settings(
+    db: &mut Connection<DB>,
+    user_id: i32,
+    id: i32,
+) -> anyhow::Result<Option<LeaderboardSettings>> {
+    use crate::schema::contests;
+
+    let settings = contests::dsl::contests
+        .select((contests::leaderboard, contests::leaderboard_hide_teams, contests::leaderboard_hide_owner))
+        .filter(contests::id.eq(id))
+        .filter(contests::owner_id.eq(user_id))
+        .filter(contests::active.eq(true))
+        .load::<LeaderboardSettings>(db)
+        .await?;
+
+    Ok(settings.get(0).copied())
+}
+
+pub async fn set_leaderboard_settings(
+    db: &mut Connection<DB>,
+    user_id: i32,
+    id: i32,
+    settings: &LeaderboardSettings,
+) -> Result<()> {
+    use crate::schema::contests;
+
+    let updated = update(contests::dsl::contests)
+        .filter(contests::id.eq(id))
+        .filter(contests::owner_id.eq(user_id))
+        .filter(contests::active.eq(true))
+        .set((
+            contests::leaderboard.eq(settings.opt_in),
+            contests::leaderboard_hide_teams.eq(settings.hide_teams),
+            contests::leaderboard_hide_owner.eq(settings.hide_owner),
+        ))
+        .execute(db)
+        .await
+        .attach_info(Status::InternalServerError, "Errore riscontrato durante l'operazione")?;
+
+    if updated == 0 {
+        return Err(anyhow!("Contest {} not found for user {}", id, user_id))
+            .attach_info(Status::NotFound, "La gara non esiste o non ti appartiene");
+    }
+
+    Ok(())
+}
+
+/// The sessions of a simulation and the teams of the original contest which count for the drift, as their sessions and
+/// names
+type DriftTeams = (Vec<i32>, Vec<(Option<i32>, String)>);
+
+/// The teams counting for the drift of a simulation, which together with the settings decide the scores of the teams
+/// of the users. `None` if bots or ghosts count for the drift, as they are different in each simulation
+fn drift_teams(contest: &Contest) -> Option<DriftTeams> {
+    let counted: Vec<_> = contest.teams
+        .iter()
+        .filter(|team| team.is_fake && (!team.hidden || contest.hidden_drift))
+        .collect();
+
+    if counted.iter().any(|team| !team.is_real()) {
+        return None;
+    }
+
+    let mut names: Vec<(Option<i32>, String)> = counted
+        .into_iter()
+        .map(|team| (team.phiquadro_sess, team.name.clone()))
+        .collect();
+    names.sort_unstable();
+
+    Some((contest.phiquadro_sessions.clone(), names))
+}
+
+/// Builds the leaderboard of the PhiQuadro contest a simulation was imported from, among the simulations which opted
+/// in, this one included in any case. Only the simulations with the same settings and the same teams counting for
+/// the drift are compared
+pub async fn fetch_leaderboard(db: &mut Connection<DB>, user_id: i32, id: i32) -> Result<Leaderboard> {
+    use crate::schema::{contests, users};
+
+    let Some(contest) = fetch_contest(db, user_id, id)
+        .await
+        .attach_info(Status::InternalServerError, "Errore riscontrato durante l'operazione")?
+    else {
+        return Err(anyhow!("Contest {} not found for user {}", id, user_id))
+            .attach_info(Status::NotFound, "La gara non esiste o non ti appartiene");
+    };
+
+    let Some(phiquadro_id) = contest.phiquadro_id else {
+        return Err(anyhow!("Contest {} was not imported from PhiQuadro", id))
+            .attach_info(
+                Status::UnprocessableEntity,
+                "Solo le gare importate da PhiQuadro hanno una classifica comune",
+            );
+    };
+
+    let Some(teams) = drift_teams(&contest) else {
+        return Err(anyhow!("Contest {} has bots or ghosts counting for the drift", id))
+            .attach_info(
+                Status::UnprocessableEntity,
+                "Le gare con bot o squadre fantasma non hanno una classifica comune",
+            );
+    };
+
+    info!("Building the leaderboard of PhiQuadro contest {} for contest {}", phiquadro_id, id);
+
+    let question_bonus: Vec<Option<i32>> = contest.question_bonus.iter().map(|&bonus| Some(bonus)).collect();
+    let contest_bonus: Vec<Option<i32>> = contest.contest_bonus.iter().map(|&bonus| Some(bonus)).collect();
+
+    let mut query = contests::dsl::contests
+        .inner_join(users::table)
+        .select((
+            contests::id,
+            users::username,
+            contests::leaderboard_hide_teams,
+            contests::leaderboard_hide_owner,
+        ))
+        .filter(contests::active.eq(true))
+        .filter(contests::leaderboard.eq(true).or(contests::id.eq(id)))
+        .filter(contests::phiquadro_id.eq(phiquadro_id))
+        .filter(contests::duration.eq(contest.duration.num_seconds() as i32))
+        .filter(contests::drift.eq(contest.drift))
+        .filter(contests::drift_time.eq(contest.drift_time.num_seconds() as i32))
+        .filter(contests::jolly_time.eq(contest.jolly_time.num_seconds() as i32))
+        .filter(contests::start_offset.eq(contest.start_offset.num_seconds() as i32))
+        .filter(contests::question_bonus.eq(question_bonus))
+        .filter(contests::contest_bonus.eq(contest_bonus))
+        .into_boxed();
+
+    query = match contest.phiquadro_sess {
+        Some(sess) => query.filter(contests::phiquadro_sess.eq(sess)),
+        None => query.filter(contests::phiquadro_sess.is_null()),
+    };
+
+    let simulations = query
+        .load::<(i32, String, bool, bool)>(db)
+        .await
+        .attach_info(Status::InternalServerError, "Errore riscontrato durante l'operazione")?;
+
+    let mut entries = vec![];
+    let mut ended = 0;
+    for (simulation_id, username, hide_teams, hide_owner) in simulations {
+        let Some(simulation) = fetch_contest_unchecked(db, simulation_id)
+            .await
+            .attach_info(Status::InternalServerError, "Errore riscontrato durante l'operazione")?
+        else {
+            continue;
+        };
+
+        // Only the final scores are compared
+        if !is_over(&simulation) || drift_teams(&simulation).as_ref() != Some(&teams) {
+            continue;
+        }
+        ended += 1;
+
+        let simulation = fetch_final_ranking(db, simulation)
+            .await
+            .attach_info(Status::InternalServerError, "Errore riscontrato durante l'operazione")?;
+
+        let own = simulation.owner_id == user_id;
+        for team in simulation.teams.iter().filter(|team| !team.is_fake) {
+            entries.push(LeaderboardEntry {
+                rank: 0,
+                team_name: (own || !hide_teams).then(|| team.name.clone()),
+                owner: (own || !hide_owner).then(|| username.clone()),
+                score: team.score,
+                simulation_rank: simulation.teams.iter().filter(|other| other.score > team.score).count() + 1,
+                start_time: simulation.join_time(),
+                own,
+            });
+        }
+    }
+
+    entries.sort_by_key(|entry| -entry.score);
+    for i in 0..entries.len() {
+        entries[i].rank = if i > 0 && entries[i - 1].score == entries[i].score { entries[i - 1].rank } else { i + 1 };
+    }
+
+    Ok(Leaderboard {
+        phiquadro_id,
+        phiquadro_sess: contest.phiquadro_sess,
+        simulations: ended,
+        entries,
+    })
+}
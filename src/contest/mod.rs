@@ -1,4 +1,18 @@
+pub mod bots;
 pub mod contest;
+pub mod export;
 pub mod fetch;
+pub mod ghosts;
 pub mod import;
+pub mod interchange;
+pub mod leaderboard;
 pub mod pages;
+pub mod printouts;
+pub mod ranking;
+pub mod report;
+pub mod scorecard;
+pub mod series;
+pub mod selection;
+pub mod snapshot;
+pub mod spectators;
+pub mod stats;
@@ -1,36 +1,108 @@
 use anyhow::anyhow;
 use diesel::{ExpressionMethods, QueryDsl};
 use rocket::http::Status;
-use rocket::Route;
+use rocket::{Route, State};
 use rocket_db_pools::diesel::prelude::RunQueryDsl;
 use rocket_db_pools::Connection;
 use rocket_dyn_templates::context;
 use rocket_dyn_templates::Template;
 
-use super::fetch::{fetch_contest, fetch_contest_with_ranking};
+use super::fetch::{fetch_contest, fetch_contest_with_public_ranking, fetch_contest_with_ranking, is_frozen, is_over};
+use super::ghosts::fetch_ghost_sources;
+use super::leaderboard::{fetch_leaderboard, fetch_leaderboard_settings};
+use super::report::fetch_report;
+use super::scorecard::fetch_scorecard;
+use super::snapshot::fetch_final_ranking_time;
+use super::spectators::{contest_clock, fetch_spectated_contest, fetch_spectator_token};
+use super::stats::fetch_questions_stats;
 use crate::api::ApiUser;
 use crate::error::IntoStatusResult;
-use crate::{model, DB};
+use crate::{model, Config, DB};
 
 #[get("/create")]
-async fn create_contest(api_user: Option<ApiUser>) -> Result<Template, Status> {
+async fn create_contest(api_user: Option<ApiUser>, config: &State<Config>) -> Result<Template, Status> {
+    let phiquadro_enabled = config.phiquadro.is_some();
+
     match api_user {
-        Some(user) => Ok(Template::render("create", context! { user })),
+        Some(user) => Ok(Template::render("create", context! { user, phiquadro_enabled })),
         None => Err(Status::Unauthorized),
     }
 }
 
-#[get("/contest/<id>")]
-pub async fn show_contest(id: i32, user: Option<ApiUser>, mut db: Connection<DB>) -> Result<Template, Status> {
+/// With `frozen`, the owner sees the ranking as the spectators do
+#[get("/contest/<id>?<frozen>")]
+pub async fn show_contest(
+    id: i32,
+    frozen: bool,
+    user: Option<ApiUser>,
+    mut db: Connection<DB>,
+) -> Result<Template, Status> {
     let Some(user) = user else {
         return Err(Status::Unauthorized)
     };
 
-    match fetch_contest_with_ranking(&mut db, user.user_id, id)
+    let contest = if frozen {
+        fetch_contest_with_public_ranking(&mut db, id)
+            .await
+            .attach_info(Status::InternalServerError, "")?
+            .filter(|contest| contest.owner_id == user.user_id)
+    } else {
+        fetch_contest_with_ranking(&mut db, user.user_id, id)
+            .await
+            .attach_info(Status::InternalServerError, "")?
+    };
+
+    match contest {
+        Some(contest) => {
+            let public_frozen = is_frozen(&contest);
+            Ok(Template::render("ranking", context! { contest, user, frozen: public_frozen, frozen_view: frozen }))
+        }
+        None => Err(Status::NotFound),
+    }
+}
+
+/// The ranking shown to whoever has the spectator token of a contest, with no need to log in
+#[get("/spectate/<token>")]
+async fn spectate_contest(token: &str, user: Option<ApiUser>, mut db: Connection<DB>) -> Result<Template, Status> {
+    let Some(id) = fetch_spectated_contest(&mut db, token)
+        .await
+        .attach_info(Status::InternalServerError, "")?
+    else {
+        return Err(Status::NotFound);
+    };
+
+    match fetch_contest_with_public_ranking(&mut db, id)
         .await
         .attach_info(Status::InternalServerError, "")?
     {
-        Some(contest) => Ok(Template::render("ranking", context! { contest, user })),
+        Some(contest) => {
+            let frozen = is_frozen(&contest);
+            Ok(Template::render("spectator", context! { contest, user, frozen, spectator: true }))
+        }
+        None => Err(Status::NotFound),
+    }
+}
+
+/// The ranking of a contest in large type, to be projected during the contest. Like the spectator view, it needs no
+/// login and shows the ranking the spectators see
+#[get("/display/<token>")]
+async fn display_contest(token: &str, mut db: Connection<DB>) -> Result<Template, Status> {
+    let Some(id) = fetch_spectated_contest(&mut db, token)
+        .await
+        .attach_info(Status::InternalServerError, "")?
+    else {
+        return Err(Status::NotFound);
+    };
+
+    match fetch_contest_with_public_ranking(&mut db, id)
+        .await
+        .attach_info(Status::InternalServerError, "")?
+    {
+        Some(contest) => {
+            let frozen = is_frozen(&contest);
+            let clock = contest_clock(&contest);
+            Ok(Template::render("display", context! { contest, frozen, clock }))
+        }
         None => Err(Status::NotFound),
     }
 }
@@ -45,7 +117,36 @@ async fn contest_settings(id: i32, user: Option<ApiUser>, mut db: Connection<DB>
         .await
         .attach_info(Status::InternalServerError, "")?
     {
-        Some(contest) => Ok(Template::render("settings", context! { contest, user })),
+        Some(contest) => {
+            let join_time = contest.join_time();
+            let over = is_over(&contest);
+            let ghost_sources = fetch_ghost_sources(&mut db, user.user_id, id)
+                .await
+                .attach_info(Status::InternalServerError, "")?;
+            let leaderboard = fetch_leaderboard_settings(&mut db, user.user_id, id)
+                .await
+                .attach_info(Status::InternalServerError, "")?;
+            let spectator_token = fetch_spectator_token(&mut db, user.user_id, id)
+                .await
+                .attach_info(Status::InternalServerError, "")?;
+            let final_ranking_time = fetch_final_ranking_time(&mut db, user.user_id, id)
+                .await
+                .attach_info(Status::InternalServerError, "")?;
+
+            Ok(Template::render(
+                "settings",
+                context! {
+                    contest,
+                    user,
+                    join_time,
+                    over,
+                    ghost_sources,
+                    leaderboard,
+                    spectator_token,
+                    final_ranking_time,
+                },
+            ))
+        }
         None => Err(Status::NotFound),
     }
 }
@@ -65,6 +166,115 @@ pub async fn submit_page(id: i32, user: Option<ApiUser>, mut db: Connection<DB>)
     }
 }
 
+/// A page where a referee types the answers read from the paper slips, one per line
+#[get("/referee/<id>")]
+async fn referee_page(id: i32, user: Option<ApiUser>, mut db: Connection<DB>) -> Result<Template, Status> {
+    let Some(user) = user else {
+        return Err(Status::Unauthorized)
+    };
+
+    match fetch_contest(&mut db, user.user_id, id)
+        .await
+        .attach_info(Status::InternalServerError, "")?
+    {
+        Some(contest) => {
+            // The teams of the user are numbered as they are listed in the page
+            let teams: Vec<_> = contest.teams.iter().filter(|team| !team.is_fake).cloned().collect();
+            Ok(Template::render("referee", context! { contest, teams, user }))
+        }
+        None => Err(Status::NotFound),
+    }
+}
+
+#[get("/stats/<id>")]
+async fn stats_page(id: i32, user: Option<ApiUser>, mut db: Connection<DB>) -> Result<Template, Status> {
+    let Some(user) = user else {
+        return Err(Status::Unauthorized)
+    };
+
+    let Some(contest) = fetch_contest(&mut db, user.user_id, id)
+        .await
+        .attach_info(Status::InternalServerError, "")?
+    else {
+        return Err(Status::NotFound);
+    };
+
+    match fetch_questions_stats(&mut db, user.user_id, id)
+        .await
+        .attach_info(Status::InternalServerError, "")?
+    {
+        Some(stats) => Ok(Template::render("stats", context! { contest, stats, user })),
+        None => Err(Status::NotFound),
+    }
+}
+
+#[get("/report/<id>")]
+async fn report_page(id: i32, user: Option<ApiUser>, mut db: Connection<DB>) -> Result<Template, Status> {
+    let Some(user) = user else {
+        return Err(Status::Unauthorized)
+    };
+
+    let Some(contest) = fetch_contest(&mut db, user.user_id, id)
+        .await
+        .attach_info(Status::InternalServerError, "")?
+    else {
+        return Err(Status::NotFound);
+    };
+
+    match fetch_report(&mut db, user.user_id, id)
+        .await
+        .attach_info(Status::InternalServerError, "")?
+    {
+        Some(report) => Ok(Template::render("report", context! { contest, report, user })),
+        None => Err(Status::NotFound),
+    }
+}
+
+#[get("/leaderboard/<id>")]
+async fn leaderboard_page(id: i32, user: Option<ApiUser>, mut db: Connection<DB>) -> Result<Template, Status> {
+    let Some(user) = user else {
+        return Err(Status::Unauthorized)
+    };
+
+    let Some(contest) = fetch_contest(&mut db, user.user_id, id)
+        .await
+        .attach_info(Status::InternalServerError, "")?
+    else {
+        return Err(Status::NotFound);
+    };
+
+    let leaderboard = fetch_leaderboard(&mut db, user.user_id, id).await?;
+
+    Ok(Template::render("leaderboard", context! { contest, leaderboard, user }))
+}
+
+#[get("/scorecard/<id>/<team_id>")]
+async fn scorecard_page(
+    id: i32,
+    team_id: i32,
+    user: Option<ApiUser>,
+    mut db: Connection<DB>,
+) -> Result<Template, Status> {
+    let Some(user) = user else {
+        return Err(Status::Unauthorized)
+    };
+
+    let Some(contest) = fetch_contest(&mut db, user.user_id, id)
+        .await
+        .attach_info(Status::InternalServerError, "")?
+    else {
+        return Err(Status::NotFound);
+    };
+
+    match fetch_scorecard(&mut db, user.user_id, id, team_id)
+        .await
+        .attach_info(Status::InternalServerError, "")?
+    {
+        Some(scorecard) => Ok(Template::render("scorecard", context! { contest, scorecard, user })),
+        None => Err(Status::NotFound),
+    }
+}
+
 #[get("/")]
 async fn show_contest_list(mut db: Connection<DB>, user: Option<ApiUser>) -> Result<Template, Status> {
     use crate::schema::contests;
@@ -101,5 +311,18 @@ async fn show_contest_list(mut db: Connection<DB>, user: Option<ApiUser>) -> Res
 }
 
 pub fn routes() -> Vec<Route> {
-    routes![create_contest, show_contest, contest_settings, submit_page, show_contest_list,]
+    routes![
+        create_contest,
+        show_contest,
+        spectate_contest,
+        display_contest,
+        contest_settings,
+        submit_page,
+        referee_page,
+        stats_page,
+        scorecard_page,
+        report_page,
+        leaderboard_page,
+        show_contest_list,
+    ]
 }
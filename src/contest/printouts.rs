@@ -0,0 +1,192 @@
+use anyhow::anyhow;
+use chrono::Utc;
+use chrono_tz::Europe::Rome;
+use rocket::http::Status;
+use rocket_db_pools::Connection;
+
+use super::contest::Contest;
+use super::fetch::{fetch_contest, is_over};
+use super::snapshot::fetch_final_ranking;
+use crate::archive::Archive;
+use crate::error::{IntoStatusResult, Result};
+use crate::pdf::{fit_text, render, Font, Page, A4};
+use crate::DB;
+
+const MARGIN: f64 = 60.0;
+const ROW_HEIGHT: f64 = 18.0;
+/// Where the first row of the table of each page of the standings is written
+const TABLE_TOP: f64 = 690.0;
+/// How many rows of the standings fit in a page, above the page number
+const ROWS_PER_PAGE: usize = 34;
+
+/// The position of a team in the final ranking, shared with the teams with the same score
+fn position(contest: &Contest, t_pos: usize) -> usize {
+    let score = contest.teams[t_pos].score;
+    contest.teams.iter().filter(|team| team.score > score).count() + 1
+}
+
+fn contest_date(contest: &Contest) -> String {
+    contest.join_time().with_timezone(&Rome).format("%d/%m/%Y").to_string()
+}
+
+/// The pages listing every team of the final ranking with its position and its score, the teams of the users in bold
+fn standings_pages(contest: &Contest) -> Vec<Page> {
+    let (width, _) = A4;
+    let positions: Vec<usize> = (0..contest.teams.len()).collect();
+    let mut chunks: Vec<&[usize]> = positions.chunks(ROWS_PER_PAGE).collect();
+    if chunks.is_empty() {
+        chunks.push(&[]);
+    }
+
+    let mut pages = vec![];
+    for (i, chunk) in chunks.iter().enumerate() {
+        let mut page = Page::new(A4);
+
+        page.text_centered(780.0, Font::Bold, 22.0, "Classifica finale");
+        page.text_centered(
+            757.0,
+            Font::Regular,
+            13.0,
+            &fit_text(&contest.name, Font::Regular, 13.0, width - 2.0 * MARGIN),
+        );
+        page.text_centered(739.0, Font::Regular, 11.0, &contest_date(contest));
+
+        page.text(MARGIN + 4.0, TABLE_TOP + 20.0, Font::Bold, 11.0, "Pos.");
+        page.text(MARGIN + 50.0, TABLE_TOP + 20.0, Font::Bold, 11.0, "Squadra");
+        page.text_right(width - MARGIN, TABLE_TOP + 20.0, Font::Bold, 11.0, "Punti");
+        page.line((MARGIN, TABLE_TOP + 13.0), (width - MARGIN, TABLE_TOP + 13.0), 1.0);
+
+        for (row, &t_pos) in chunk.iter().enumerate() {
+            let team = &contest.teams[t_pos];
+            let y = TABLE_TOP - row as f64 * ROW_HEIGHT;
+            let font = if team.is_fake { Font::Regular } else { Font::Bold };
+
+            if !team.is_fake {
+                page.fill_rectangle(MARGIN, y - 5.0, width - 2.0 * MARGIN, ROW_HEIGHT, 0.9);
+            }
+            page.text(MARGIN + 4.0, y, font, 11.0, &position(contest, t_pos).to_string());
+            page.text(
+                MARGIN + 50.0,
+                y,
+                font,
+                11.0,
+                &fit_text(&team.name, font, 11.0, width - 2.0 * MARGIN - 120.0),
+            );
+            page.text_right(width - MARGIN - 4.0, y, font, 11.0, &team.score.to_string());
+        }
+
+        page.text_centered(40.0, Font::Regular, 9.0, &format!("Pagina {} di {}", i + 1, chunks.len()));
+        pages.push(page);
+    }
+
+    pages
+}
+
+/// The participation certificate of a team of the users
+fn certificate_page(contest: &Contest, t_pos: usize) -> Page {
+    let team = &contest.teams[t_pos];
+    let mut page = Page::landscape(A4);
+    let (width, height) = (page.width, page.height);
+
+    page.rectangle(25.0, 25.0, width - 50.0, height - 50.0, 3.0);
+    page.rectangle(33.0, 33.0, width - 66.0, height - 66.0, 0.8);
+
+    page.text_centered(465.0, Font::Bold, 34.0, "Attestato di partecipazione");
+    page.text_centered(405.0, Font::Regular, 16.0, "Si attesta che la squadra");
+    page.text_centered(355.0, Font::Bold, 30.0, &fit_text(&team.name, Font::Bold, 30.0, width - 2.0 * MARGIN));
+    page.text_centered(305.0, Font::Regular, 16.0, "ha partecipato alla simulazione della gara");
+    page.text_centered(
+        275.0,
+        Font::Bold,
+        20.0,
+        &fit_text(&contest.name, Font::Bold, 20.0, width - 2.0 * MARGIN),
+    );
+    page.text_centered(
+        225.0,
+        Font::Regular,
+        16.0,
+        &format!(
+            "classificandosi in posizione {} su {} squadre con {} {}",
+            position(contest, t_pos),
+            contest.teams.len(),
+            team.score,
+            if team.score == 1 { "punto" } else { "punti" },
+        ),
+    );
+
+    page.text(MARGIN + 20.0, 110.0, Font::Regular, 13.0, &contest_date(contest));
+    page.line((width - MARGIN - 220.0, 105.0), (width - MARGIN - 20.0, 105.0), 0.8);
+    page.text_right(width - MARGIN - 20.0, 88.0, Font::Regular, 11.0, "Firma");
+
+    page
+}
+
+/// A name which can be used safely as the name of a file on any system
+fn file_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' { c } else { '_' })
+        .collect();
+
+    name.trim_matches('_').to_string()
+}
+
+/// The final ranking of a contest, failing if the contest doesn't exist or isn't over yet
+async fn fetch_printable_ranking(db: &mut Connection<DB>, user_id: i32, id: i32) -> Result<Contest> {
+    let Some(contest) = fetch_contest(db, user_id, id)
+        .await
+        .attach_info(Status::InternalServerError, "Errore riscontrato durante l'operazione")?
+    else {
+        return Err(anyhow!("Contest {} not found for user {}", id, user_id))
+            .attach_info(Status::NotFound, "La gara non esiste o non ti appartiene");
+    };
+
+    if !is_over(&contest) {
+        return Err(anyhow!("Contest {} is not over", id))
+            .attach_info(Status::Forbidden, "La gara non è ancora terminata");
+    }
+
+    fetch_final_ranking(db, contest)
+        .await
+        .attach_info(Status::InternalServerError, "Errore riscontrato durante l'operazione")
+}
+
+/// A single document with the standings of an ended contest followed by the certificates of the teams of the users
+pub async fn fetch_results_pdf(db: &mut Connection<DB>, user_id: i32, id: i32) -> Result<Vec<u8>> {
+    let contest = fetch_printable_ranking(db, user_id, id).await?;
+
+    let mut pages = standings_pages(&contest);
+    pages.extend(
+        (0..contest.teams.len())
+            .filter(|&t_pos| !contest.teams[t_pos].is_fake)
+            .map(|t_pos| certificate_page(&contest, t_pos)),
+    );
+
+    Ok(render(&format!("Risultati - {}", contest.name), &pages))
+}
+
+/// An archive with the standings of an ended contest and a separate document for the certificate of each team of the
+/// users
+pub async fn fetch_results_zip(db: &mut Connection<DB>, user_id: i32, id: i32) -> Result<Vec<u8>> {
+    let contest = fetch_printable_ranking(db, user_id, id).await?;
+    let now = Utc::now().with_timezone(&Rome);
+
+    let mut archive = Archive::new();
+    archive.add(
+        "classifica.pdf",
+        &render(&format!("Classifica - {}", contest.name), &standings_pages(&contest)),
+        &now,
+    );
+
+    for t_pos in (0..contest.teams.len()).filter(|&t_pos| !contest.teams[t_pos].is_fake) {
+        let team = &contest.teams[t_pos];
+        archive.add(
+            // Numbered as in the ranking, which also keeps apart the teams with the same name
+            &format!("attestati/{:02}_{}.pdf", t_pos + 1, file_name(&team.name)),
+            &render(&format!("Attestato - {}", team.name), &[certificate_page(&contest, t_pos)]),
+            &now,
+        );
+    }
+
+    Ok(archive.finish())
+}
@@ -0,0 +1,199 @@
+use std::cmp;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::Serialize;
+
+use super::contest::{Contest, QuestionStatus};
+use crate::model::{real_to_contest_time, ContestJollies, ContestSubmissions};
+
+/// A single contribution to the score of a team, so that the sum of the lines of a team is its score
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ScoreLine {
+    /// The points every team starts with
+    Base { points: i64 },
+    /// A submission on a question (starting from 0) at a time in seconds of contest time. A correct answer gains the
+    /// value of the question plus the bonus of its tier only the first time, a wrong one always loses 10 points
+    Submission {
+        question: usize,
+        time: i64,
+        answer: i32,
+        correct: bool,
+        points: i64,
+        value: Option<i64>,
+        tier: Option<usize>,
+        bonus: Option<i64>,
+    },
+    /// The jolly doubles the points of its question, so it gains (or loses) them again
+    Jolly { question: usize, points: i64 },
+    /// The bonus for being the `tier`-th team to solve every question
+    ContestBonus { tier: usize, points: i64 },
+}
+
+/// Computes the scores of the questions and of the teams from the activity until `now` and sorts the teams by score.
+/// The teams must be ordered by position and the submissions by time, hidden teams only count for the drift if the
+/// contest says so.
+pub fn compute_ranking(
+    contest: &mut Contest,
+    submissions: &[ContestSubmissions],
+    jollies: &[ContestJollies],
+    now: DateTime<Utc>,
+) {
+    compute_ranking_with_lines(contest, submissions, jollies, now, |_, _| {});
+}
+
+/// Same as [`compute_ranking`], also passing each line of the score of a team, with its position, to `record`
+pub fn compute_ranking_with_lines(
+    contest: &mut Contest,
+    submissions: &[ContestSubmissions],
+    jollies: &[ContestJollies],
+    now: DateTime<Utc>,
+    mut record: impl FnMut(usize, ScoreLine),
+) {
+    let Contest {
+        questions,
+        teams,
+        drift,
+        drift_time,
+        start_time,
+        question_bonus,
+        contest_bonus,
+        hidden_drift,
+        time_scale,
+        ..
+    } = contest;
+
+    let drift_no = *drift;
+    let mut correct = vec![0; questions.len()];
+    let mut wrong = vec![vec![false; questions.len()]; teams.len()];
+    let mut drift = vec![*drift_time; questions.len()];
+    let counts_for_drift: Vec<bool> = teams.iter().map(|team| !team.hidden || *hidden_drift).collect();
+
+    for submission in submissions {
+        let q_pos = submission.question_pos as usize;
+        let t_pos = submission.team_pos as usize;
+        let sub_time = real_to_contest_time(submission.sub_time - *start_time, *time_scale);
+
+        if !counts_for_drift[t_pos] {
+            continue;
+        }
+
+        if submission.given_answer == submission.correct_answer {
+            correct[q_pos] += 1;
+            if correct[q_pos] >= drift_no {
+                drift[q_pos] = cmp::min(drift[q_pos], sub_time);
+            }
+        } else {
+            if sub_time < *drift_time && submission.given_answer != submission.correct_answer {
+                if correct[q_pos] == 0 && !wrong[t_pos][q_pos] {
+                    questions[q_pos].score += 2;
+                }
+                wrong[t_pos][q_pos] = true;
+            }
+        }
+    }
+
+    let elapsed = real_to_contest_time(now - *start_time, *time_scale);
+    for i in 0..questions.len() {
+        questions[i].score += cmp::min(drift[i], elapsed).num_minutes().max(0);
+        questions[i].lock_time = drift[i];
+        if elapsed >= drift[i] {
+            questions[i].locked = true;
+        }
+    }
+
+    let mut question_solves = vec![0; questions.len()];
+    let mut team_solves = vec![0; teams.len()];
+    let mut solves = 0;
+
+    for (t_pos, team) in teams.iter().enumerate() {
+        if !team.hidden {
+            record(t_pos, ScoreLine::Base { points: team.score });
+        }
+    }
+
+    for submission in submissions {
+        let q_pos = submission.question_pos as usize;
+        let t_pos = submission.team_pos as usize;
+
+        if teams[t_pos].hidden {
+            continue;
+        }
+
+        let mut line = ScoreLine::Submission {
+            question: q_pos,
+            time: real_to_contest_time(submission.sub_time - *start_time, *time_scale).num_seconds(),
+            answer: submission.given_answer,
+            correct: submission.given_answer == submission.correct_answer,
+            points: 0,
+            value: None,
+            tier: None,
+            bonus: None,
+        };
+        let mut bonus_line = None;
+
+        if submission.given_answer == submission.correct_answer {
+            if
+                teams[t_pos].questions[q_pos].status != QuestionStatus::Solved
+                && teams[t_pos].questions[q_pos].status != QuestionStatus::JustSolved
+            {
+                let bonus = *question_bonus.get(question_solves[q_pos]).unwrap_or(&0) as i64;
+                teams[t_pos].questions[q_pos].score += questions[q_pos].score + bonus;
+
+                if let ScoreLine::Submission { points, value, tier, bonus: line_bonus, .. } = &mut line {
+                    *points = questions[q_pos].score + bonus;
+                    *value = Some(questions[q_pos].score);
+                    *tier = Some(question_solves[q_pos] + 1);
+                    *line_bonus = Some(bonus);
+                }
+
+                question_solves[q_pos] += 1;
+                team_solves[t_pos] += 1;
+
+                if team_solves[t_pos] == questions.len() {
+                    let points = *contest_bonus.get(solves).unwrap_or(&0) as i64;
+                    teams[t_pos].score += points;
+                    solves += 1;
+                    bonus_line = Some(ScoreLine::ContestBonus { tier: solves, points });
+                }
+
+                teams[t_pos].questions[q_pos].status =if submission.sub_time >= now - TimeDelta::minutes(1) {
+                    QuestionStatus::JustSolved
+                } else {
+                    QuestionStatus::Solved
+                };
+            }
+        } else {
+            if teams[t_pos].questions[q_pos].status == QuestionStatus::NotAttempted {
+                teams[t_pos].questions[q_pos].status = QuestionStatus::Attempted;
+            }
+            teams[t_pos].questions[q_pos].score -= 10;
+
+            if let ScoreLine::Submission { points, .. } = &mut line {
+                *points = -10;
+            }
+        }
+
+        record(t_pos, line);
+        if let Some(bonus_line) = bonus_line {
+            record(t_pos, bonus_line);
+        }
+    }
+
+    for jolly in jollies {
+        let t_pos = jolly.team_pos as usize;
+        let q_pos = jolly.question_pos as usize;
+
+        if !teams[t_pos].hidden {
+            record(t_pos, ScoreLine::Jolly { question: q_pos, points: teams[t_pos].questions[q_pos].score });
+        }
+        teams[t_pos].questions[q_pos].score *= 2;
+        teams[t_pos].questions[q_pos].jolly = true;
+    }
+
+    for team in teams.iter_mut() {
+        team.score += team.questions.iter().map(|q| q.score).sum::<i64>();
+    }
+
+    teams.sort_unstable_by_key(|team| -team.score);
+}
@@ -0,0 +1,258 @@
+use chrono::{DateTime, Utc};
+use rocket_db_pools::Connection;
+use serde::Serialize;
+
+use super::contest::Contest;
+use super::fetch::{fetch_activity, fetch_contest, ranking_time};
+use super::ranking::compute_ranking;
+use crate::model::{real_to_contest_time, ContestJollies, ContestSubmissions};
+use crate::DB;
+
+/// A question solved by at most this fraction of the real teams is considered rare
+const RARE_SOLVE_FRACTION: f64 = 0.25;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct QuestionComparison {
+    /// Starting from 1, as shown in the ranking
+    pub position: usize,
+    /// Seconds of contest time at which the team solved the question, if it did
+    pub time: Option<i64>,
+    pub real_solves: u32,
+    /// The median of the times at which the real teams solved the question, if any did
+    pub real_median: Option<i64>,
+    /// Whether the team solved a question few real teams solved
+    pub rare: bool,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct TeamComparison {
+    pub team_id: i32,
+    pub team_name: String,
+    pub score: i64,
+    /// The rank of the team among the real teams, starting from 1 and shared with the teams with the same score
+    pub rank: usize,
+    /// The percentage of real teams which scored less than the team
+    pub percentile: f64,
+    pub questions: Vec<QuestionComparison>,
+}
+
+/// How the teams of the users would have placed among the real teams of the original contest
+#[derive(Clone, Debug, Serialize)]
+pub struct ContestReport {
+    pub real_teams: usize,
+    pub teams: Vec<TeamComparison>,
+}
+
+/// The contest time at which each team solved each question
+fn solve_times(contest: &Contest, submissions: &[ContestSubmissions]) -> Vec<Vec<Option<i64>>> {
+    let mut times = vec![vec![None; contest.questions.len()]; contest.teams.len()];
+    for submission in submissions {
+        let time = &mut times[submission.team_pos as usize][submission.question_pos as usize];
+        if submission.given_answer == submission.correct_answer && time.is_none() {
+            let sub_time = real_to_contest_time(submission.sub_time - contest.start_time, contest.time_scale);
+            *time = Some(sub_time.num_seconds());
+        }
+    }
+
+    times
+}
+
+fn median(mut values: Vec<i64>) -> Option<i64> {
+    if values.is_empty() {
+        return None;
+    }
+
+    values.sort_unstable();
+    let mid = values.len() / 2;
+    Some(if values.len().is_multiple_of(2) { (values[mid - 1] + values[mid]) / 2 } else { values[mid] })
+}
+
+/// Compares each team of the users with the real teams of the original contest, every team selected or not, as if
+/// it had been the only one to join it. The teams must be ordered by position and the submissions by time
+pub fn compute_report(
+    contest: &Contest,
+    submissions: &[ContestSubmissions],
+    jollies: &[ContestJollies],
+    now: DateTime<Utc>,
+) -> ContestReport {
+    let real: Vec<usize> = (0..contest.teams.len()).filter(|&t_pos| contest.teams[t_pos].is_real()).collect();
+    let times = solve_times(contest, submissions);
+
+    let real_solves: Vec<Vec<i64>> = (0..contest.questions.len())
+        .map(|q_pos| real.iter().filter_map(|&t_pos| times[t_pos][q_pos]).collect())
+        .collect();
+
+    let mut teams = vec![];
+    for t_pos in (0..contest.teams.len()).filter(|&t_pos| !contest.teams[t_pos].is_fake) {
+        let team_id = contest.teams[t_pos].id;
+
+        let mut ranking = contest.clone();
+        for (other, team) in ranking.teams.iter_mut().enumerate() {
+            team.hidden = other != t_pos && !contest.teams[other].is_real();
+        }
+        ranking.hidden_drift = false;
+        compute_ranking(&mut ranking, submissions, jollies, now);
+        ranking.teams.retain(|team| !team.hidden);
+
+        let Some(team) = ranking.teams.iter().find(|team| team.id == team_id) else {
+            continue;
+        };
+        let ahead = ranking.teams.iter().filter(|other| other.score > team.score).count();
+        let beaten = ranking.teams.iter().filter(|other| other.score < team.score).count();
+
+        let questions = (0..contest.questions.len())
+            .map(|q_pos| {
+                let solves = real_solves[q_pos].len();
+
+                QuestionComparison {
+                    position: q_pos + 1,
+                    time: times[t_pos][q_pos],
+                    real_solves: solves as u32,
+                    real_median: median(real_solves[q_pos].clone()),
+                    rare: times[t_pos][q_pos].is_some() && (solves as f64) <= RARE_SOLVE_FRACTION * real.len() as f64,
+                }
+            })
+            .collect();
+
+        teams.push(TeamComparison {
+            team_id,
+            team_name: team.name.clone(),
+            score: team.score,
+            rank: ahead + 1,
+            percentile: if real.is_empty() { 100.0 } else { 100.0 * beaten as f64 / real.len() as f64 },
+            questions,
+        });
+    }
+
+    ContestReport { real_teams: real.len(), teams }
+}
+
+/// Computes the report of a contest at the time its ranking is shown at. Returns `None` if the contest doesn't exist
+pub async fn fetch_report(db: &mut Connection<DB>, user_id: i32, id: i32) -> anyhow::Result<Option<ContestReport>> {
+    let Some(contest) = fetch_contest(db, user_id, id).await? else {
+        return Ok(None);
+    };
+
+    let now = ranking_time(&contest);
+    let (submissions, jollies) = fetch_activity(db, id, now).await?;
+
+    Ok(Some(compute_report(&contest, &submissions, &jollies, now)))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+
+    use super::*;
+    use crate::contest::contest::{Question, Team, TeamQuestion};
+
+    fn team(pos: usize, is_fake: bool, is_bot: bool) -> Team {
+        Team {
+            id: pos as i32 + 1,
+            name: format!("Squadra {}", pos + 1),
+            is_fake,
+            phiquadro_sess: None,
+            hidden: false,
+            is_bot,
+            is_ghost: false,
+            score: 20,
+            questions: vec![TeamQuestion::default(); 2],
+        }
+    }
+
+    /// Two questions worth the same, without bonuses, and three real teams, a bot, another real team and two teams
+    /// of the users, in this order
+    fn contest() -> Contest {
+        Contest {
+            id: 1,
+            name: "Gara".to_string(),
+            phiquadro_id: None,
+            phiquadro_sess: None,
+            phiquadro_sessions: vec![],
+            questions: (0..2)
+                .map(|i| Question { id: i + 1, answer: 1, score: 20, locked: false, lock_time: Duration::zero() })
+                .collect(),
+            teams: vec![
+                team(0, true, false),
+                team(1, true, false),
+                team(2, true, false),
+                team(3, true, true),
+                team(4, true, false),
+                team(5, false, false),
+                team(6, false, false),
+            ],
+            duration: Duration::minutes(120),
+            start_time: Utc.with_ymd_and_hms(2025, 3, 1, 10, 0, 0).unwrap(),
+            drift: 100,
+            drift_time: Duration::minutes(20),
+            jolly_time: Duration::minutes(10),
+            question_bonus: vec![0; 10],
+            contest_bonus: vec![0; 10],
+            hidden_drift: false,
+            time_scale: 1.0,
+            paused_at: None,
+            start_offset: Duration::zero(),
+            owner_id: 1,
+            freeze_time: None,
+            revealed: false,
+        }
+    }
+
+    fn solve(contest: &Contest, team_pos: i32, question_pos: i32, minute: i64) -> ContestSubmissions {
+        ContestSubmissions {
+            given_answer: 1,
+            sub_time: contest.real_time(Duration::minutes(minute)),
+            correct_answer: 1,
+            question_pos,
+            team_pos,
+            is_fake: contest.teams[team_pos as usize].is_fake,
+            contest_id: contest.id,
+        }
+    }
+
+    #[test]
+    fn median_of_even_and_odd_counts() {
+        assert_eq!(median(vec![]), None);
+        assert_eq!(median(vec![30, 10, 20]), Some(20));
+        assert_eq!(median(vec![40, 10, 30, 20]), Some(25));
+    }
+
+    #[test]
+    fn compare_with_the_real_teams() {
+        let contest = contest();
+        let mut submissions = vec![
+            solve(&contest, 3, 0, 1),
+            solve(&contest, 3, 1, 1),
+            solve(&contest, 0, 0, 10),
+            solve(&contest, 0, 1, 20),
+            solve(&contest, 1, 0, 30),
+            solve(&contest, 2, 1, 40),
+            solve(&contest, 5, 0, 50),
+        ];
+        submissions.sort_by_key(|submission| submission.sub_time);
+
+        let report = compute_report(&contest, &submissions, &[], contest.end_time());
+        assert_eq!(report.real_teams, 4);
+        assert_eq!(report.teams.len(), 2);
+
+        // Solving one question, the team ties with the two real teams which did the same, while the bot doesn't count
+        let team = &report.teams[0];
+        assert_eq!(team.team_id, 6);
+        assert_eq!(team.rank, 2);
+        assert_eq!(team.percentile, 25.0);
+
+        // Bots don't count for the medians, which fall between the two real solves
+        let questions = &team.questions;
+        assert_eq!(questions[0].time, Some(50 * 60));
+        assert_eq!((questions[0].real_solves, questions[0].real_median), (2, Some(20 * 60)));
+        assert_eq!((questions[1].real_solves, questions[1].real_median), (2, Some(30 * 60)));
+        assert_eq!(questions[1].time, None);
+        assert!(!questions[0].rare && !questions[1].rare);
+
+        // Without solving anything, the team ties with the last real team
+        let team = &report.teams[1];
+        assert_eq!(team.team_id, 7);
+        assert_eq!(team.rank, 4);
+        assert_eq!(team.percentile, 0.0);
+    }
+}
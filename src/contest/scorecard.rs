@@ -0,0 +1,96 @@
+use rocket_db_pools::Connection;
+use serde::Serialize;
+
+use super::fetch::{fetch_activity, fetch_contest, ranking_time};
+use super::ranking::{compute_ranking_with_lines, ScoreLine};
+use crate::DB;
+
+/// The lines of the score of a team on a question
+#[derive(Clone, Debug, Serialize)]
+pub struct QuestionCard {
+    /// Starting from 1, as shown in the ranking
+    pub position: usize,
+    pub value: i64,
+    pub jolly: bool,
+    pub points: i64,
+    pub lines: Vec<ScoreLine>,
+}
+
+/// The breakdown of the score of a team, whose lines add up to the score shown in the ranking
+#[derive(Clone, Debug, Serialize)]
+pub struct Scorecard {
+    pub team_id: i32,
+    pub team_name: String,
+    pub is_fake: bool,
+    pub rank: usize,
+    pub score: i64,
+    pub base: i64,
+    pub questions: Vec<QuestionCard>,
+    pub contest_bonus: Option<ScoreLine>,
+}
+
+/// Computes the scorecard of a team of a contest, as shown in its ranking. Returns `None` if the contest or the team
+/// don't exist or the team is hidden from the ranking
+pub async fn fetch_scorecard(
+    db: &mut Connection<DB>,
+    user_id: i32,
+    id: i32,
+    team_id: i32,
+) -> anyhow::Result<Option<Scorecard>> {
+    let Some(mut contest) = fetch_contest(db, user_id, id).await? else {
+        return Ok(None);
+    };
+
+    let Some(position) = contest.teams.iter().position(|team| team.id == team_id && !team.hidden) else {
+        return Ok(None);
+    };
+
+    let now = ranking_time(&contest);
+    let (submissions, jollies) = fetch_activity(db, id, now).await?;
+
+    let mut lines = vec![];
+    compute_ranking_with_lines(&mut contest, &submissions, &jollies, now, |t_pos, line| {
+        if t_pos == position {
+            lines.push(line);
+        }
+    });
+    contest.teams.retain(|team| !team.hidden);
+
+    let Some((rank, team)) = contest.teams.iter().enumerate().find(|(_, team)| team.id == team_id) else {
+        return Ok(None);
+    };
+
+    let mut scorecard = Scorecard {
+        team_id,
+        team_name: team.name.clone(),
+        is_fake: team.is_fake,
+        rank: rank + 1,
+        score: team.score,
+        base: 0,
+        questions: contest.questions
+            .iter()
+            .zip(&team.questions)
+            .enumerate()
+            .map(|(i, (question, team_question))| QuestionCard {
+                position: i + 1,
+                value: question.score,
+                jolly: team_question.jolly,
+                points: team_question.score,
+                lines: vec![],
+            })
+            .collect(),
+        contest_bonus: None,
+    };
+
+    for line in lines {
+        match line {
+            ScoreLine::Base { points } => scorecard.base = points,
+            ScoreLine::Submission { question, .. } | ScoreLine::Jolly { question, .. } => {
+                scorecard.questions[question].lines.push(line);
+            }
+            ScoreLine::ContestBonus { .. } => scorecard.contest_bonus = Some(line),
+        }
+    }
+
+    Ok(Some(scorecard))
+}
@@ -0,0 +1,157 @@
+use std::collections::HashSet;
+
+use anyhow::anyhow;
+use diesel::{update, ExpressionMethods, QueryDsl};
+use regex::{Regex, RegexBuilder};
+use rocket::http::Status;
+use rocket_db_pools::diesel::prelude::RunQueryDsl;
+use rocket_db_pools::Connection;
+use serde::Deserialize;
+use tracing::info;
+
+use super::fetch::{fetch_activity, fetch_contest};
+use super::ranking::compute_ranking;
+use crate::error::{IntoStatusResult, Result};
+use crate::DB;
+
+/// Which fake teams are replayed in a contest, the others are hidden from the ranking
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum TeamSelection {
+    /// Every fake team
+    All,
+    /// The fake teams with one of the given names
+    Teams { teams: Vec<String> },
+    /// The fake teams which ended the original contest in the first `rank` places
+    Rank { rank: u32 },
+    /// The fake teams whose name matches the given regular expression, ignoring case
+    Name { pattern: String },
+}
+
+impl TeamSelection {
+    /// Checks that the selection can be applied to any contest
+    pub fn validate(&self) -> Result<()> {
+        if let TeamSelection::Name { pattern } = self {
+            build_pattern(pattern)?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the teams chosen by name are among the given fake teams
+    pub fn check_teams<'a>(&self, names: impl IntoIterator<Item = &'a str>) -> Result<()> {
+        let TeamSelection::Teams { teams } = self else {
+            return Ok(());
+        };
+
+        let found: HashSet<&str> = names.into_iter().map(|name| name.trim()).collect();
+        let missing: Vec<&str> = teams
+            .iter()
+            .map(|name| name.trim())
+            .filter(|name| !found.contains(name))
+            .collect();
+        if !missing.is_empty() {
+            return Err(anyhow!("Teams {:?} are not in the contest", missing))
+                .attach_info(Status::UnprocessableEntity, &format!("Squadre non trovate: {}", missing.join(", ")));
+        }
+
+        Ok(())
+    }
+}
+
+fn build_pattern(pattern: &str) -> Result<Regex> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .build()
+        .attach_info(Status::UnprocessableEntity, "L'espressione per il nome delle squadre non è valida")
+}
+
+/// Hides the fake teams of a contest which are not selected and shows the others, also choosing whether hidden teams
+/// count for the drift. Returns the number of fake teams selected
+pub async fn apply_team_selection(
+    db: &mut Connection<DB>,
+    user_id: i32,
+    id: i32,
+    selection: &TeamSelection,
+    hidden_drift: bool,
+) -> Result<usize> {
+    use crate::schema::{contests, teams};
+
+    let Some(mut contest) = fetch_contest(db, user_id, id)
+        .await
+        .attach_info(Status::InternalServerError, "Errore incontrato durante la selezione delle squadre")?
+    else {
+        return Err(anyhow!("Contest {} not found for user {}", id, user_id))
+            .attach_info(Status::NotFound, "La gara non esiste o non ti appartiene");
+    };
+
+    info!("Selecting the teams of contest {}: {:?}", id, selection);
+
+    let selected: Vec<i32> = match selection {
+        TeamSelection::All => contest.teams.iter().filter(|team| team.is_fake).map(|team| team.id).collect(),
+        TeamSelection::Teams { teams } => {
+            // Only fake teams can be selected, a user team with the same name doesn't count
+            selection.check_teams(contest.teams.iter().filter(|team| team.is_fake).map(|team| team.name.as_str()))?;
+
+            let names: HashSet<&str> = teams.iter().map(|name| name.trim()).collect();
+            contest.teams
+                .iter()
+                .filter(|team| team.is_fake && names.contains(team.name.trim()))
+                .map(|team| team.id)
+                .collect()
+        }
+        TeamSelection::Rank { rank } => {
+            // The final ranking of the original contest, without the teams of the users
+            let end = contest.end_time();
+            let (submissions, jollies) = fetch_activity(db, id, end)
+                .await
+                .attach_info(Status::InternalServerError, "Errore incontrato durante la selezione delle squadre")?;
+
+            for team in contest.teams.iter_mut() {
+                team.hidden = !team.is_fake;
+            }
+            contest.hidden_drift = false;
+
+            compute_ranking(&mut contest, &submissions, &jollies, end);
+
+            contest.teams
+                .iter()
+                .filter(|team| team.is_fake)
+                .take(*rank as usize)
+                .map(|team| team.id)
+                .collect()
+        }
+        TeamSelection::Name { pattern } => {
+            let pattern = build_pattern(pattern)?;
+            contest.teams
+                .iter()
+                .filter(|team| team.is_fake && pattern.is_match(&team.name))
+                .map(|team| team.id)
+                .collect()
+        }
+    };
+
+    update(
+        teams::dsl::teams
+            .filter(teams::contest_id.eq(id))
+            .filter(teams::is_fake.eq(true))
+    )
+        .set(teams::hidden.eq(true))
+        .execute(db)
+        .await
+        .attach_info(Status::InternalServerError, "Errore incontrato durante la selezione delle squadre")?;
+
+    update(teams::dsl::teams.filter(teams::id.eq_any(&selected)))
+        .set(teams::hidden.eq(false))
+        .execute(db)
+        .await
+        .attach_info(Status::InternalServerError, "Errore incontrato durante la selezione delle squadre")?;
+
+    update(contests::dsl::contests.filter(contests::id.eq(id)))
+        .set(contests::hidden_drift.eq(hidden_drift))
+        .execute(db)
+        .await
+        .attach_info(Status::InternalServerError, "Errore incontrato durante la selezione delle squadre")?;
+
+    Ok(selected.len())
+}
@@ -0,0 +1,216 @@
+use std::cmp;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use rocket_db_pools::Connection;
+use serde::Serialize;
+
+use super::contest::Contest;
+use super::fetch::{fetch_activity, fetch_contest, ranking_time};
+use crate::model::{real_to_contest_time, ContestJollies, ContestSubmissions};
+use crate::DB;
+
+/// When the scores of the teams are sampled
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sampling {
+    /// At every minute of contest time
+    Minute,
+    /// At every submission or jolly
+    Event,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct TeamSeries {
+    pub team_id: i32,
+    pub team_name: String,
+    pub is_fake: bool,
+    pub scores: Vec<i64>,
+    /// Starting from 1, teams with the same score share the rank
+    pub ranks: Vec<usize>,
+}
+
+/// The scores and the ranks of the teams shown in the ranking at some instants of a contest
+#[derive(Clone, Debug, Serialize)]
+pub struct Series {
+    /// Seconds of contest time from the start
+    pub times: Vec<i64>,
+    pub teams: Vec<TeamSeries>,
+}
+
+enum Event<'a> {
+    Submission(&'a ContestSubmissions),
+    Jolly(&'a ContestJollies),
+}
+
+/// Computes the scores and the ranks of the teams from the start of the contest until `until`, or until its end if
+/// it comes first. The activity is scanned once, keeping the state of the ranking updated, and at each sample the
+/// score of a team is its fixed points plus the current value of the questions it solved, so the last sample is the
+/// same as the ranking computed at `until`.
+///
+/// The teams must be ordered by position and the submissions by time, as for the ranking.
+pub fn compute_series(
+    contest: &Contest,
+    submissions: &[ContestSubmissions],
+    jollies: &[ContestJollies],
+    until: DateTime<Utc>,
+    sampling: Sampling,
+) -> Series {
+    let contest_time = |time: DateTime<Utc>| real_to_contest_time(time - contest.start_time, contest.time_scale);
+
+    let mut events: Vec<(TimeDelta, Event)> = submissions
+        .iter()
+        .map(|submission| (contest_time(submission.sub_time), Event::Submission(submission)))
+        .chain(jollies.iter().map(|jolly| (contest_time(jolly.sub_time), Event::Jolly(jolly))))
+        .collect();
+    // Stable, so the submissions keep their order
+    events.sort_by_key(|&(time, _)| time);
+
+    let end = cmp::min(contest_time(until), contest.duration);
+    let mut times: Vec<TimeDelta> = match sampling {
+        Sampling::Minute => (0..=end.num_minutes()).map(TimeDelta::minutes).collect(),
+        Sampling::Event => [TimeDelta::zero()]
+            .into_iter()
+            .chain(events.iter().map(|&(time, _)| time).filter(|&time| time > TimeDelta::zero() && time <= end))
+            .collect(),
+    };
+    times.dedup();
+    // The last sample is exactly at the end, replacing one in the same second
+    if times.last().is_some_and(|last| last.num_seconds() == end.num_seconds()) {
+        times.pop();
+    }
+    times.push(end);
+    if end < TimeDelta::zero() {
+        times.clear();
+    }
+
+    let questions_no = contest.questions.len();
+    let teams = &contest.teams;
+    let counts_for_drift: Vec<bool> = teams.iter().map(|team| !team.hidden || contest.hidden_drift).collect();
+
+    // The state of the drift, as in the ranking
+    let mut correct = vec![0; questions_no];
+    let mut wrong = vec![vec![false; questions_no]; teams.len()];
+    let mut drift = vec![contest.drift_time; questions_no];
+    let mut wrong_bonus = vec![0i64; questions_no];
+
+    // The state of the teams: the points which don't depend on the value of the questions, and for each question
+    // whether it was solved with which bonus, the number of wrong answers and the jolly multiplier
+    let mut fixed: Vec<i64> = teams.iter().map(|team| team.score).collect();
+    let mut solved: Vec<Vec<Option<i64>>> = vec![vec![None; questions_no]; teams.len()];
+    let mut errors = vec![vec![0i64; questions_no]; teams.len()];
+    let mut multiplier = vec![vec![1i64; questions_no]; teams.len()];
+    let mut question_solves = vec![0; questions_no];
+    let mut team_solves = vec![0; teams.len()];
+    let mut solves = 0;
+
+    let visible: Vec<usize> = (0..teams.len()).filter(|&t_pos| !teams[t_pos].hidden).collect();
+    let mut series: Vec<TeamSeries> = visible
+        .iter()
+        .map(|&t_pos| TeamSeries {
+            team_id: teams[t_pos].id,
+            team_name: teams[t_pos].name.clone(),
+            is_fake: teams[t_pos].is_fake,
+            scores: Vec::with_capacity(times.len()),
+            ranks: Vec::with_capacity(times.len()),
+        })
+        .collect();
+
+    let mut next_event = 0;
+    for &time in &times {
+        while next_event < events.len() && events[next_event].0 <= time {
+            let (sub_time, event) = &events[next_event];
+            next_event += 1;
+
+            let submission = match event {
+                Event::Submission(submission) => submission,
+                Event::Jolly(jolly) => {
+                    multiplier[jolly.team_pos as usize][jolly.question_pos as usize] = 2;
+                    continue;
+                }
+            };
+
+            let q_pos = submission.question_pos as usize;
+            let t_pos = submission.team_pos as usize;
+            let is_correct = submission.given_answer == submission.correct_answer;
+
+            if counts_for_drift[t_pos] {
+                if is_correct {
+                    correct[q_pos] += 1;
+                    if correct[q_pos] >= contest.drift {
+                        drift[q_pos] = cmp::min(drift[q_pos], *sub_time);
+                    }
+                } else if *sub_time < contest.drift_time {
+                    if correct[q_pos] == 0 && !wrong[t_pos][q_pos] {
+                        wrong_bonus[q_pos] += 2;
+                    }
+                    wrong[t_pos][q_pos] = true;
+                }
+            }
+
+            if teams[t_pos].hidden {
+                continue;
+            }
+
+            if !is_correct {
+                errors[t_pos][q_pos] += 1;
+            } else if solved[t_pos][q_pos].is_none() {
+                solved[t_pos][q_pos] = Some(*contest.question_bonus.get(question_solves[q_pos]).unwrap_or(&0) as i64);
+                question_solves[q_pos] += 1;
+                team_solves[t_pos] += 1;
+
+                if team_solves[t_pos] == questions_no {
+                    fixed[t_pos] += *contest.contest_bonus.get(solves).unwrap_or(&0) as i64;
+                    solves += 1;
+                }
+            }
+        }
+
+        let values: Vec<i64> = (0..questions_no)
+            .map(|q_pos| {
+                contest.questions[q_pos].score + wrong_bonus[q_pos] + cmp::min(drift[q_pos], time).num_minutes().max(0)
+            })
+            .collect();
+
+        let scores: Vec<i64> = visible
+            .iter()
+            .map(|&t_pos| {
+                fixed[t_pos] + (0..questions_no)
+                    .map(|q_pos| {
+                        let points = solved[t_pos][q_pos].map_or(0, |bonus| values[q_pos] + bonus);
+                        multiplier[t_pos][q_pos] * (points - 10 * errors[t_pos][q_pos])
+                    })
+                    .sum::<i64>()
+            })
+            .collect();
+
+        let mut sorted = scores.clone();
+        sorted.sort_unstable_by_key(|&score| -score);
+
+        for (team, &score) in series.iter_mut().zip(&scores) {
+            team.scores.push(score);
+            team.ranks.push(sorted.partition_point(|&other| other > score) + 1);
+        }
+    }
+
+    Series {
+        times: times.iter().map(|time| time.num_seconds()).collect(),
+        teams: series,
+    }
+}
+
+/// Computes the series of a contest until the time its ranking is shown at. Returns `None` if the contest doesn't
+/// exist
+pub async fn fetch_series(
+    db: &mut Connection<DB>,
+    user_id: i32,
+    id: i32,
+    sampling: Sampling,
+) -> anyhow::Result<Option<Series>> {
+    let Some(contest) = fetch_contest(db, user_id, id).await? else {
+        return Ok(None);
+    };
+
+    let now = ranking_time(&contest);
+    let (submissions, jollies) = fetch_activity(db, id, now).await?;
+
+    Ok(Some(compute_series(&contest, &submissions, &jollies, now, sampling)))
+}
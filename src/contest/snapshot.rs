@@ -0,0 +1,243 @@
+use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+use diesel::{ExpressionMethods, QueryDsl};
+use rocket::http::Status;
+use rocket_db_pools::diesel::prelude::{AsyncConnection, RunQueryDsl};
+use rocket_db_pools::diesel::scoped_futures::ScopedFutureExt;
+use rocket_db_pools::Connection;
+use tracing::info;
+
+use super::contest::{Contest, QuestionStatus, Team, TeamQuestion};
+use super::fetch::{fetch_activity, fetch_contest, is_over};
+use super::ranking::compute_ranking;
+use crate::error::{IntoStatusResult, Result};
+use crate::model::{FinalRanking, FinalRankingTeam};
+use crate::DB;
+
+fn status_code(status: QuestionStatus) -> i16 {
+    match status {
+        QuestionStatus::NotAttempted => 0,
+        QuestionStatus::Attempted => 1,
+        // Nothing was just solved once the contest is over
+        QuestionStatus::JustSolved | QuestionStatus::Solved => 2,
+    }
+}
+
+fn status_from_code(code: i16) -> QuestionStatus {
+    match code {
+        1 => QuestionStatus::Attempted,
+        2 => QuestionStatus::Solved,
+        _ => QuestionStatus::NotAttempted,
+    }
+}
+
+/// Computes the ranking of a contest at its end from its activity, leaving out the hidden teams
+async fn compute_final_ranking(db: &mut Connection<DB>, mut contest: Contest) -> anyhow::Result<Contest> {
+    let end = contest.end_time();
+    let (submissions, jollies) = fetch_activity(db, contest.id, end).await?;
+
+    compute_ranking(&mut contest, &submissions, &jollies, end);
+    contest.teams.retain(|team| !team.hidden);
+
+    Ok(contest)
+}
+
+/// Saves the final ranking of a contest, replacing the previous one if `replace`, otherwise keeping the one saved
+/// meanwhile by another request
+async fn save_final_ranking(db: &mut Connection<DB>, contest: &Contest, replace: bool) -> anyhow::Result<()> {
+    use crate::schema::{final_ranking_teams, final_rankings};
+
+    let ranking = FinalRanking {
+        contest_id: contest.id,
+        computed_at: chrono::offset::Utc::now(),
+        question_scores: contest.questions.iter().map(|question| Some(question.score as i32)).collect(),
+        question_locked: contest.questions.iter().map(|question| Some(question.locked)).collect(),
+    };
+
+    let teams: Vec<FinalRankingTeam> = contest.teams
+        .iter()
+        .enumerate()
+        .map(|(i, team)| FinalRankingTeam {
+            contest_id: contest.id,
+            rank: i as i32 + 1,
+            team_id: team.id,
+            team_name: team.name.clone(),
+            is_fake: team.is_fake,
+            is_bot: team.is_bot,
+            is_ghost: team.is_ghost,
+            phiquadro_sess: team.phiquadro_sess,
+            score: team.score as i32,
+            question_scores: team.questions.iter().map(|question| Some(question.score as i32)).collect(),
+            question_statuses: team.questions.iter().map(|question| Some(status_code(question.status))).collect(),
+            jollies: team.questions.iter().map(|question| Some(question.jolly)).collect(),
+        })
+        .collect();
+
+    db.transaction::<_, anyhow::Error, _>(|conn| async move {
+        if replace {
+            diesel::delete(final_rankings::dsl::final_rankings)
+                .filter(final_rankings::contest_id.eq(ranking.contest_id))
+                .execute(conn)
+                .await?;
+        }
+
+        let inserted = diesel::insert_into(final_rankings::table)
+            .values(&ranking)
+            .on_conflict_do_nothing()
+            .execute(conn)
+            .await?;
+
+        if inserted > 0 {
+            diesel::insert_into(final_ranking_teams::table)
+                .values(&teams)
+                .execute(conn)
+                .await?;
+        }
+
+        Ok(())
+    }.scope_boxed())
+    .await
+}
+
+/// Loads the saved final ranking of a contest, if there is one
+async fn load_final_ranking(db: &mut Connection<DB>, contest: &Contest) -> anyhow::Result<Option<Contest>> {
+    use crate::schema::{final_ranking_teams, final_rankings};
+
+    let ranking = final_rankings::dsl::final_rankings
+        .filter(final_rankings::contest_id.eq(contest.id))
+        .load::<FinalRanking>(db)
+        .await?;
+
+    let Some(ranking) = ranking.get(0) else {
+        return Ok(None);
+    };
+
+    let teams = final_ranking_teams::dsl::final_ranking_teams
+        .select((
+            final_ranking_teams::contest_id,
+            final_ranking_teams::rank,
+            final_ranking_teams::team_id,
+            final_ranking_teams::team_name,
+            final_ranking_teams::is_fake,
+            final_ranking_teams::is_bot,
+            final_ranking_teams::is_ghost,
+            final_ranking_teams::phiquadro_sess,
+            final_ranking_teams::score,
+            final_ranking_teams::question_scores,
+            final_ranking_teams::question_statuses,
+            final_ranking_teams::jollies,
+        ))
+        .filter(final_ranking_teams::contest_id.eq(contest.id))
+        .order(final_ranking_teams::rank.asc())
+        .load::<FinalRankingTeam>(db)
+        .await?;
+
+    let mut contest = contest.clone();
+
+    for (question, (&score, &locked)) in contest.questions
+        .iter_mut()
+        .zip(ranking.question_scores.iter().zip(&ranking.question_locked))
+    {
+        question.score = score.expect("Question score can't be null") as i64;
+        question.locked = locked.expect("Question lock can't be null");
+    }
+
+    contest.teams = teams
+        .into_iter()
+        .map(|team| Team {
+            id: team.team_id,
+            name: team.team_name,
+            is_fake: team.is_fake,
+            phiquadro_sess: team.phiquadro_sess,
+            hidden: false,
+            is_bot: team.is_bot,
+            is_ghost: team.is_ghost,
+            score: team.score as i64,
+            questions: team.question_scores
+                .iter()
+                .zip(&team.question_statuses)
+                .zip(&team.jollies)
+                .map(|((&score, &status), &jolly)| TeamQuestion {
+                    score: score.expect("Question score can't be null") as i64,
+                    jolly: jolly.expect("Jolly can't be null"),
+                    status: status_from_code(status.expect("Question status can't be null")),
+                })
+                .collect(),
+        })
+        .collect();
+
+    Ok(Some(contest))
+}
+
+/// The ranking of an ended contest, as it was saved at the end. It is saved now if it wasn't yet, so that later
+/// changes to the settings or to the teams of the contest don't alter its results
+pub async fn fetch_final_ranking(db: &mut Connection<DB>, contest: Contest) -> anyhow::Result<Contest> {
+    if let Some(ranking) = load_final_ranking(db, &contest).await? {
+        return Ok(ranking);
+    }
+
+    info!("Saving the final ranking of contest {}", contest.id);
+
+    let ranking = compute_final_ranking(db, contest).await?;
+    save_final_ranking(db, &ranking, false).await?;
+
+    // Another request could have saved it in the meantime
+    Ok(load_final_ranking(db, &ranking).await?.unwrap_or(ranking))
+}
+
+/// When the final ranking of a contest was saved, if it was
+pub async fn fetch_final_ranking_time(
+    db: &mut Connection<DB>,
+    user_id: i32,
+    id: i32,
+) -> anyhow::Result<Option<DateTime<Utc>>> {
+    use crate::schema::{contests, final_rankings};
+
+    let time = final_rankings::dsl::final_rankings
+        .inner_join(contests::table)
+        .select(final_rankings::computed_at)
+        .filter(final_rankings::contest_id.eq(id))
+        .filter(contests::owner_id.eq(user_id))
+        .load::<DateTime<Utc>>(db)
+        .await?;
+
+    Ok(time.get(0).copied())
+}
+
+/// Replaces the final ranking of an ended contest with one computed from its current settings and teams
+pub async fn recompute_final_ranking(db: &mut Connection<DB>, user_id: i32, id: i32) -> Result<()> {
+    let Some(contest) = fetch_contest(db, user_id, id)
+        .await
+        .attach_info(Status::InternalServerError, "Errore riscontrato durante l'operazione")?
+    else {
+        return Err(anyhow!("Contest {} not found for user {}", id, user_id))
+            .attach_info(Status::NotFound, "La gara non esiste o non ti appartiene");
+    };
+
+    if !is_over(&contest) {
+        return Err(anyhow!("Contest {} is not over", id))
+            .attach_info(Status::Forbidden, "La gara non è ancora terminata");
+    }
+
+    info!("Recomputing the final ranking of contest {}", id);
+
+    let ranking = compute_final_ranking(db, contest)
+        .await
+        .attach_info(Status::InternalServerError, "Errore riscontrato durante l'operazione")?;
+    save_final_ranking(db, &ranking, true)
+        .await
+        .attach_info(Status::InternalServerError, "Errore riscontrato durante l'operazione")?;
+
+    Ok(())
+}
+
+/// Forgets the final ranking of a contest which is going to be played again
+pub async fn delete_final_ranking(db: &mut Connection<DB>, id: i32) -> anyhow::Result<()> {
+    use crate::schema::final_rankings;
+
+    diesel::delete(final_rankings::dsl::final_rankings.filter(final_rankings::contest_id.eq(id)))
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,150 @@
+use anyhow::anyhow;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use diesel::{update, ExpressionMethods, QueryDsl};
+use ring::rand::{SecureRandom, SystemRandom};
+use rocket::http::Status;
+use rocket_db_pools::diesel::prelude::RunQueryDsl;
+use rocket_db_pools::Connection;
+use serde::Serialize;
+use tracing::info;
+
+use super::contest::Contest;
+use super::fetch::{fetch_contest, is_frozen};
+use crate::error::{IntoStatusResult, Result};
+use crate::DB;
+
+const SPECTATOR_TOKEN_LEN: usize = 32;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClockState {
+    NotStarted,
+    Running,
+    Paused,
+    Over,
+}
+
+/// What the clock shown to the spectators says: the real seconds left until the user teams join the contest if it
+/// didn't start yet, otherwise until its end
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct Clock {
+    pub state: ClockState,
+    pub seconds: i64,
+}
+
+pub fn contest_clock(contest: &Contest) -> Clock {
+    let now = contest.paused_at.unwrap_or_else(chrono::offset::Utc::now);
+
+    let (state, seconds) = if now < contest.join_time() {
+        (ClockState::NotStarted, (contest.join_time() - now).num_seconds())
+    } else if now >= contest.end_time() {
+        (ClockState::Over, 0)
+    } else if contest.paused_at.is_some() {
+        (ClockState::Paused, (contest.end_time() - now).num_seconds())
+    } else {
+        (ClockState::Running, (contest.end_time() - now).num_seconds())
+    };
+
+    Clock { state, seconds }
+}
+
+/// The token giving access to the public view of a contest, if its owner created one
+pub async fn fetch_spectator_token(db: &mut Connection<DB>, user_id: i32, id: i32) -> anyhow::Result<Option<String>> {
+    use crate::schema::contests;
+
+    let token = contests::dsl::contests
+        .select(contests::spectator_token)
+        .filter(contests::id.eq(id))
+        .filter(contests::owner_id.eq(user_id))
+        .filter(contests::active.eq(true))
+        .load::<Option<String>>(db)
+        .await?;
+
+    Ok(token.into_iter().next().flatten())
+}
+
+/// The contest a spectator token gives access to, if any
+pub async fn fetch_spectated_contest(db: &mut Connection<DB>, token: &str) -> anyhow::Result<Option<i32>> {
+    use crate::schema::contests;
+
+    let id = contests::dsl::contests
+        .select(contests::id)
+        .filter(contests::spectator_token.eq(token))
+        .filter(contests::active.eq(true))
+        .load::<i32>(db)
+        .await?;
+
+    Ok(id.get(0).copied())
+}
+
+/// Gives a contest a new spectator token, so that the links shared before stop working
+pub async fn create_spectator_token(db: &mut Connection<DB>, user_id: i32, id: i32) -> Result<String> {
+    let mut bytes = [0u8; SPECTATOR_TOKEN_LEN];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| anyhow!("Could not generate a spectator token"))
+        .attach_info(Status::InternalServerError, "Errore riscontrato durante l'operazione")?;
+    let token = URL_SAFE_NO_PAD.encode(bytes);
+
+    info!("Creating a spectator token for contest {}", id);
+
+    set_spectator_token(db, user_id, id, Some(&token)).await?;
+
+    Ok(token)
+}
+
+pub async fn delete_spectator_token(db: &mut Connection<DB>, user_id: i32, id: i32) -> Result<()> {
+    info!("Deleting the spectator token of contest {}", id);
+
+    set_spectator_token(db, user_id, id, None).await
+}
+
+async fn set_spectator_token(db: &mut Connection<DB>, user_id: i32, id: i32, token: Option<&str>) -> Result<()> {
+    use crate::schema::contests;
+
+    let updated = update(contests::dsl::contests)
+        .filter(contests::id.eq(id))
+        .filter(contests::owner_id.eq(user_id))
+        .filter(contests::active.eq(true))
+        .set(contests::spectator_token.eq(token))
+        .execute(db)
+        .await
+        .attach_info(Status::InternalServerError, "Errore riscontrato durante l'operazione")?;
+
+    if updated == 0 {
+        return Err(anyhow!("Contest {} not found for user {}", id, user_id))
+            .attach_info(Status::NotFound, "La gara non esiste o non ti appartiene");
+    }
+
+    Ok(())
+}
+
+/// Shows the live ranking to the spectators for the rest of the contest
+pub async fn reveal_ranking(db: &mut Connection<DB>, user_id: i32, id: i32) -> Result<()> {
+    use crate::schema::contests;
+
+    let Some(contest) = fetch_contest(db, user_id, id)
+        .await
+        .attach_info(Status::InternalServerError, "Errore riscontrato durante l'operazione")?
+    else {
+        return Err(anyhow!("Contest {} not found for user {}", id, user_id))
+            .attach_info(Status::NotFound, "La gara non esiste o non ti appartiene");
+    };
+
+    if !is_frozen(&contest) {
+        return Err(anyhow!("The ranking of contest {} is not frozen", id))
+            .attach_info(Status::Conflict, "La classifica non è congelata");
+    }
+
+    info!("Revealing the ranking of contest {}", id);
+
+    update(contests::dsl::contests)
+        .filter(contests::id.eq(id))
+        .set(contests::revealed.eq(true))
+        .execute(db)
+        .await
+        .attach_info(Status::InternalServerError, "Errore riscontrato durante l'operazione")?;
+
+    Ok(())
+}
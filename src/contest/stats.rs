@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+
+use rocket_db_pools::Connection;
+use serde::Serialize;
+
+use super::contest::Team;
+use super::fetch::{fetch_activity, fetch_contest, ranking_time};
+use super::ranking::compute_ranking;
+use crate::model::real_to_contest_time;
+use crate::DB;
+
+/// How many of the most common wrong answers are reported
+const WRONG_ANSWERS_NO: usize = 5;
+
+/// Counts of submissions, split between the real teams of the original contest, the bots, the ghosts and the teams
+/// of the users
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct AttemptCounts {
+    pub real: u32,
+    pub bot: u32,
+    pub ghost: u32,
+    pub user: u32,
+}
+
+impl AttemptCounts {
+    fn add(&mut self, team: &Team) {
+        if team.is_bot {
+            self.bot += 1;
+        } else if team.is_ghost {
+            self.ghost += 1;
+        } else if team.is_fake {
+            self.real += 1;
+        } else {
+            self.user += 1;
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct FirstSolver {
+    pub team_id: i32,
+    pub team_name: String,
+    pub is_fake: bool,
+    /// Seconds of contest time from the start
+    pub time: i64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct WrongAnswer {
+    pub answer: i32,
+    pub count: u32,
+}
+
+/// What happened on a question of a contest until now, only counting the teams shown in the ranking
+#[derive(Clone, Debug, Serialize)]
+pub struct QuestionStats {
+    /// Starting from 1, as shown in the ranking
+    pub position: usize,
+    pub answer: i32,
+    pub correct: AttemptCounts,
+    pub wrong: AttemptCounts,
+    pub first_solver: Option<FirstSolver>,
+    /// Seconds of contest time at which the drift locked the score, if it did already
+    pub locked_at: Option<i64>,
+    pub score: i64,
+    pub jollies: u32,
+    pub wrong_answers: Vec<WrongAnswer>,
+}
+
+/// Computes the statistics of every question of a contest, as shown in its ranking. Returns `None` if the contest
+/// doesn't exist
+pub async fn fetch_questions_stats(
+    db: &mut Connection<DB>,
+    user_id: i32,
+    id: i32,
+) -> anyhow::Result<Option<Vec<QuestionStats>>> {
+    let Some(mut contest) = fetch_contest(db, user_id, id).await? else {
+        return Ok(None);
+    };
+
+    let now = ranking_time(&contest);
+    let (submissions, jollies) = fetch_activity(db, id, now).await?;
+
+    // The ranking sorts the teams, while the activity refers to their positions
+    let teams = contest.teams.clone();
+    compute_ranking(&mut contest, &submissions, &jollies, now);
+
+    let mut stats: Vec<QuestionStats> = contest.questions
+        .iter()
+        .enumerate()
+        .map(|(i, question)| QuestionStats {
+            position: i + 1,
+            answer: question.answer,
+            correct: AttemptCounts::default(),
+            wrong: AttemptCounts::default(),
+            first_solver: None,
+            locked_at: question.locked.then(|| question.lock_time.num_seconds()),
+            score: question.score,
+            jollies: 0,
+            wrong_answers: vec![],
+        })
+        .collect();
+    let mut wrong_answers: Vec<HashMap<i32, u32>> = vec![HashMap::new(); stats.len()];
+
+    for submission in &submissions {
+        let team = &teams[submission.team_pos as usize];
+        if team.hidden {
+            continue;
+        }
+
+        let q_pos = submission.question_pos as usize;
+        if submission.given_answer == submission.correct_answer {
+            stats[q_pos].correct.add(team);
+            if stats[q_pos].first_solver.is_none() {
+                stats[q_pos].first_solver = Some(FirstSolver {
+                    team_id: team.id,
+                    team_name: team.name.clone(),
+                    is_fake: team.is_fake,
+                    time: real_to_contest_time(submission.sub_time - contest.start_time, contest.time_scale)
+                        .num_seconds(),
+                });
+            }
+        } else {
+            stats[q_pos].wrong.add(team);
+            *wrong_answers[q_pos].entry(submission.given_answer).or_default() += 1;
+        }
+    }
+
+    for jolly in &jollies {
+        if !teams[jolly.team_pos as usize].hidden {
+            stats[jolly.question_pos as usize].jollies += 1;
+        }
+    }
+
+    for (question, answers) in stats.iter_mut().zip(wrong_answers) {
+        question.wrong_answers = answers
+            .into_iter()
+            .map(|(answer, count)| WrongAnswer { answer, count })
+            .collect();
+        question.wrong_answers.sort_unstable_by_key(|wrong| (-(wrong.count as i64), wrong.answer));
+        question.wrong_answers.truncate(WRONG_ANSWERS_NO);
+    }
+
+    Ok(Some(stats))
+}
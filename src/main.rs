@@ -5,17 +5,24 @@
 #[macro_use]
 extern crate rocket;
 
+use std::collections::HashMap;
 use std::env;
 
+use rocket::fairing::AdHoc;
+use rocket::figment::providers::Env;
 use rocket::fs::{relative, FileServer};
 use rocket_db_pools::diesel;
 use rocket_db_pools::Database;
 use rocket_dyn_templates::Template;
+use serde::Deserialize;
+use tracing::warn;
 
 mod api;
+mod archive;
 mod contest;
 mod error;
 mod model;
+mod pdf;
 mod schema;
 mod policy;
 
@@ -23,23 +30,51 @@ mod policy;
 #[database("gas_simulator")]
 pub struct DB(diesel::PgPool);
 
+#[derive(Deserialize)]
 struct PhiQuadroLogin {
     username: String,
     password: String,
 }
 
+#[derive(Deserialize)]
+pub struct Config {
+    /// The credentials to import contests from PhiQuadro, if the integration is enabled
+    phiquadro: Option<PhiQuadroLogin>,
+}
+
+/// The PhiQuadro credentials from USERNAME and PASSWORD, which older deployments may still set. Only used if both
+/// are set, as USERNAME alone is often set by the system for other reasons
+fn legacy_phiquadro_login() -> Option<(String, String)> {
+    match (env::var("USERNAME"), env::var("PASSWORD")) {
+        (Ok(username), Ok(password)) => Some((username, password)),
+        _ => None,
+    }
+}
+
 #[launch]
 fn rocket() -> _ {
     tracing_subscriber::fmt().init();
-    dotenvy::dotenv().expect("could not open .env file");
 
-    rocket::build()
+    // A .env file is optional, it can be used to set PHIQUADRO_* or ROCKET_* variables
+    dotenvy::dotenv().ok();
+
+    let mut figment = rocket::Config::figment()
+        .merge(Env::prefixed("PHIQUADRO_").map(|key| format!("phiquadro.{key}").into()).global());
+
+    if !figment.contains("phiquadro") {
+        if let Some((username, password)) = legacy_phiquadro_login() {
+            warn!(
+                "Using the deprecated USERNAME and PASSWORD for PhiQuadro, set PHIQUADRO_USERNAME and \
+                PHIQUADRO_PASSWORD instead"
+            );
+            figment = figment.merge(("phiquadro", HashMap::from([("username", username), ("password", password)])));
+        }
+    }
+
+    rocket::custom(figment)
         .attach(DB::init())
         .attach(Template::fairing())
-        .manage(PhiQuadroLogin {
-            username: env::var("USERNAME").expect("please set a username in .env"),
-            password: env::var("PASSWORD").expect("please set a password in .env"),
-        })
+        .attach(AdHoc::config::<Config>())
         .mount("/", FileServer::new(relative!("/static"), rocket::fs::Options::None))
         .mount("/", contest::pages::routes())
         .mount("/api", api::routes())
@@ -1,13 +1,13 @@
 use chrono::{DateTime, TimeDelta, Utc};
-use diesel::{data_types::PgInterval, Insertable, Queryable, Selectable};
+use diesel::{Insertable, Queryable, Selectable};
 use serde::Serialize;
 
 #[derive(Debug, Clone, Queryable, Selectable, Insertable)]
 #[diesel(table_name = crate::schema::contests)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
 pub struct Contest {
-    pub phiquadro_id: i32,
-    pub phiquadro_sess: i32,
+    pub phiquadro_id: Option<i32>,
+    pub phiquadro_sess: Option<i32>,
     pub contest_name: String,
     pub duration: i32,
     pub start_time: DateTime<Utc>,
@@ -20,6 +20,12 @@ pub struct Contest {
     pub question_bonus: Vec<Option<i32>>,
     pub contest_bonus: Vec<Option<i32>>,
     pub owner_id: i32,
+    pub hidden_drift: bool,
+    pub time_scale: f64,
+    pub paused_at: Option<DateTime<Utc>>,
+    pub start_offset: i32,
+    pub freeze_time: Option<i32>,
+    pub revealed: bool,
 }
 
 #[derive(Debug, Clone, Copy, Queryable, Selectable, Insertable)]
@@ -39,6 +45,10 @@ pub struct Team {
     pub is_fake: bool,
     pub position: i32,
     pub contest_id: i32,
+    pub phiquadro_sess: Option<i32>,
+    pub hidden: bool,
+    pub is_bot: bool,
+    pub is_ghost: bool,
 }
 
 #[derive(Debug, Clone, Queryable, Selectable, Insertable)]
@@ -60,6 +70,34 @@ pub struct Jolly {
     pub question_id: i32,
 }
 
+#[derive(Debug, Clone, Queryable, Selectable, Insertable)]
+#[diesel(table_name = crate::schema::final_rankings)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct FinalRanking {
+    pub contest_id: i32,
+    pub computed_at: DateTime<Utc>,
+    pub question_scores: Vec<Option<i32>>,
+    pub question_locked: Vec<Option<bool>>,
+}
+
+#[derive(Debug, Clone, Queryable, Selectable, Insertable)]
+#[diesel(table_name = crate::schema::final_ranking_teams)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct FinalRankingTeam {
+    pub contest_id: i32,
+    pub rank: i32,
+    pub team_id: i32,
+    pub team_name: String,
+    pub is_fake: bool,
+    pub is_bot: bool,
+    pub is_ghost: bool,
+    pub phiquadro_sess: Option<i32>,
+    pub score: i32,
+    pub question_scores: Vec<Option<i32>>,
+    pub question_statuses: Vec<Option<i16>>,
+    pub jollies: Vec<Option<bool>>,
+}
+
 #[derive(Debug, Clone, Queryable, Selectable, Insertable)]
 #[diesel(table_name = crate::schema::users)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
@@ -100,8 +138,8 @@ pub struct ContestJollies {
 #[derive(Queryable, Serialize, Clone)]
 pub struct ContestWithId {
     pub id: i32,
-    pub phiquadro_id: i32,
-    pub phiquadro_sess: i32,
+    pub phiquadro_id: Option<i32>,
+    pub phiquadro_sess: Option<i32>,
     pub contest_name: String,
     pub duration: i32,
     pub start_time: DateTime<Utc>,
@@ -120,15 +158,18 @@ pub struct TeamWithId {
     pub is_fake: bool,
     pub position: i32,
     pub contest_id: i32,
+    pub phiquadro_sess: Option<i32>,
+    pub hidden: bool,
+    pub is_bot: bool,
+    pub is_ghost: bool,
 }
 
-pub fn timedelta_to_pg_interval(delta: TimeDelta) -> PgInterval {
-    const MILLISECONDS_IN_DAYS: i64 = 1_000 * 86_400;
+/// Converts a span of contest time into the real time it lasts when the contest is replayed at the given speed
+pub fn contest_to_real_time(delta: TimeDelta, time_scale: f64) -> TimeDelta {
+    TimeDelta::milliseconds((delta.num_milliseconds() as f64 / time_scale).round() as i64)
+}
 
-    let millisecond = delta.num_milliseconds();
-    PgInterval {
-        microseconds: (millisecond % MILLISECONDS_IN_DAYS) * 1_000,
-        days: ((millisecond / MILLISECONDS_IN_DAYS) % 30) as i32,
-        months: ((millisecond / MILLISECONDS_IN_DAYS) / 30) as i32,
-    }
+/// Converts a span of real time into the contest time elapsed meanwhile when the contest is replayed at the given speed
+pub fn real_to_contest_time(delta: TimeDelta, time_scale: f64) -> TimeDelta {
+    TimeDelta::milliseconds((delta.num_milliseconds() as f64 * time_scale).round() as i64)
 }
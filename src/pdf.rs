@@ -0,0 +1,312 @@
+use std::fmt::Write;
+
+/// Width and height of an A4 page in points
+pub const A4: (f64, f64) = (595.0, 842.0);
+
+/// Widths of the printable ASCII characters in Helvetica, in thousandths of the font size
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556, 556, 556, 556, 556,
+    556, 556, 556, 278, 278, 584, 584, 584, 556, 1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833,
+    722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556, 333, 556, 556, 500, 556,
+    556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334,
+    260, 334, 584,
+];
+
+/// Widths of the printable ASCII characters in Helvetica Bold, in thousandths of the font size
+const HELVETICA_BOLD_WIDTHS: [u16; 95] = [
+    278, 333, 474, 556, 556, 889, 722, 238, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556, 556, 556, 556, 556,
+    556, 556, 556, 333, 333, 584, 584, 584, 611, 975, 722, 722, 722, 722, 667, 611, 778, 722, 278, 556, 722, 611, 833,
+    722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 333, 278, 333, 584, 556, 333, 556, 611, 556, 611,
+    556, 333, 611, 611, 278, 278, 556, 278, 889, 611, 611, 611, 611, 389, 556, 333, 611, 556, 778, 556, 556, 500, 389,
+    280, 389, 584,
+];
+
+/// Used for the characters outside ASCII, mostly accented letters which are as wide as the letters they come from
+const DEFAULT_WIDTH: u16 = 556;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Font {
+    Regular,
+    Bold,
+}
+
+impl Font {
+    fn resource(self) -> &'static str {
+        match self {
+            Font::Regular => "F1",
+            Font::Bold => "F2",
+        }
+    }
+
+    /// The width of a text written in this font with the given size, in points
+    pub fn text_width(self, text: &str, size: f64) -> f64 {
+        let widths = match self {
+            Font::Regular => &HELVETICA_WIDTHS,
+            Font::Bold => &HELVETICA_BOLD_WIDTHS,
+        };
+
+        let width: u32 = text
+            .chars()
+            .map(|c| match c {
+                ' '..='~' => widths[c as usize - ' ' as usize],
+                _ => DEFAULT_WIDTH,
+            } as u32)
+            .sum();
+
+        width as f64 * size / 1000.0
+    }
+}
+
+/// Encodes a character as WinAnsiEncoding, the encoding of the standard fonts, which matches Latin-1 from 0xA0 on
+fn win_ansi(c: char) -> u8 {
+    match c {
+        ' '..='~' => c as u8,
+        '\u{a0}'..='\u{ff}' => c as u32 as u8,
+        '€' => 0x80,
+        '…' => 0x85,
+        '‘' => 0x91,
+        '’' => 0x92,
+        '“' => 0x93,
+        '”' => 0x94,
+        '–' => 0x96,
+        '—' => 0x97,
+        _ => b'?',
+    }
+}
+
+/// Writes a text as a PDF string literal, escaping what can't appear in it as is
+fn string_literal(text: &str) -> String {
+    let mut literal = "(".to_string();
+    for byte in text.chars().map(win_ansi) {
+        match byte {
+            b'(' | b')' | b'\\' => {
+                literal.push('\\');
+                literal.push(byte as char);
+            }
+            b' '..=b'~' => literal.push(byte as char),
+            _ => write!(literal, "\\{:03o}", byte).unwrap(),
+        }
+    }
+    literal.push(')');
+
+    literal
+}
+
+/// Shortens a text so that it fits the given width, ending it with an ellipsis if it was cut
+pub fn fit_text(text: &str, font: Font, size: f64, width: f64) -> String {
+    if font.text_width(text, size) <= width {
+        return text.to_string();
+    }
+
+    let mut fitted: String = text.to_string();
+    while !fitted.is_empty() && font.text_width(&format!("{}…", fitted), size) > width {
+        fitted.pop();
+    }
+
+    format!("{}…", fitted.trim_end())
+}
+
+/// A page of a document, with the origin in its bottom left corner
+#[derive(Clone, Debug)]
+pub struct Page {
+    pub width: f64,
+    pub height: f64,
+    content: String,
+}
+
+impl Page {
+    pub fn new((width, height): (f64, f64)) -> Self {
+        Self { width, height, content: String::new() }
+    }
+
+    pub fn landscape((width, height): (f64, f64)) -> Self {
+        Self::new((height, width))
+    }
+
+    /// Writes a text starting from the given point of its baseline
+    pub fn text(&mut self, x: f64, y: f64, font: Font, size: f64, text: &str) {
+        writeln!(
+            self.content,
+            "BT /{} {:.2} Tf {:.2} {:.2} Td {} Tj ET",
+            font.resource(),
+            size,
+            x,
+            y,
+            string_literal(text),
+        )
+        .unwrap();
+    }
+
+    /// Writes a text ending at the given point of its baseline
+    pub fn text_right(&mut self, x: f64, y: f64, font: Font, size: f64, text: &str) {
+        self.text(x - font.text_width(text, size), y, font, size, text);
+    }
+
+    /// Writes a text centered horizontally in the page
+    pub fn text_centered(&mut self, y: f64, font: Font, size: f64, text: &str) {
+        self.text((self.width - font.text_width(text, size)) / 2.0, y, font, size, text);
+    }
+
+    pub fn line(&mut self, from: (f64, f64), to: (f64, f64), width: f64) {
+        writeln!(self.content, "{:.2} w {:.2} {:.2} m {:.2} {:.2} l S", width, from.0, from.1, to.0, to.1).unwrap();
+    }
+
+    /// Draws the border of a rectangle given its bottom left corner and its size
+    pub fn rectangle(&mut self, x: f64, y: f64, width: f64, height: f64, line_width: f64) {
+        writeln!(self.content, "{:.2} w {:.2} {:.2} {:.2} {:.2} re S", line_width, x, y, width, height).unwrap();
+    }
+
+    /// Fills a rectangle with a shade of grey, from 0 for black to 1 for white
+    pub fn fill_rectangle(&mut self, x: f64, y: f64, width: f64, height: f64, grey: f64) {
+        writeln!(self.content, "q {:.2} g {:.2} {:.2} {:.2} {:.2} re f Q", grey, x, y, width, height).unwrap();
+    }
+}
+
+/// Writes a document made of the given pages, using only the standard Helvetica fonts so that nothing needs to be
+/// embedded
+pub fn render(title: &str, pages: &[Page]) -> Vec<u8> {
+    // The catalog, the page tree, the two fonts and the information come first, then each page and its content
+    let first_page = 6;
+    let kids: Vec<String> = (0..pages.len()).map(|i| format!("{} 0 R", first_page + 2 * i)).collect();
+
+    let mut objects = vec![
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), pages.len()),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>".to_string(),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold /Encoding /WinAnsiEncoding >>".to_string(),
+        format!("<< /Title {} /Producer (GAS Simulator) >>", string_literal(title)),
+    ];
+
+    for (i, page) in pages.iter().enumerate() {
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {:.2} {:.2}] \
+             /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> /Contents {} 0 R >>",
+            page.width,
+            page.height,
+            first_page + 2 * i + 1,
+        ));
+        objects.push(format!("<< /Length {} >>\nstream\n{}endstream", page.content.len(), page.content));
+    }
+
+    // The comment with bytes above 127 tells the programs transferring the file that it is binary
+    let mut pdf = "%PDF-1.4\n%\u{e2}\u{e3}\u{cf}\u{d3}\n".to_string();
+    let mut offsets = vec![];
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        write!(pdf, "{} 0 obj\n{}\nendobj\n", i + 1, object).unwrap();
+    }
+
+    let xref = pdf.len();
+    writeln!(pdf, "xref\n0 {}\n0000000000 65535 f ", objects.len() + 1).unwrap();
+    for offset in offsets {
+        writeln!(pdf, "{:010} 00000 n ", offset).unwrap();
+    }
+    write!(
+        pdf,
+        "trailer\n<< /Size {} /Root 1 0 R /Info 5 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objects.len() + 1,
+        xref,
+    )
+    .unwrap();
+
+    pdf.into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Finds the first occurrence of a sequence of bytes from the given position
+    fn find(data: &[u8], from: usize, needle: &[u8]) -> Option<usize> {
+        data[from..].windows(needle.len()).position(|window| window == needle).map(|pos| from + pos)
+    }
+
+    /// Reads the objects of a document through its cross-reference table, checking that each entry points to the
+    /// object with its number and that each stream is as long as it declares
+    fn read_objects(pdf: &[u8]) -> Vec<String> {
+        let text = String::from_utf8_lossy(pdf);
+        let tail: Vec<&str> = text.trim_end().lines().rev().take(3).collect();
+        assert_eq!(tail[0], "%%EOF");
+        let xref: usize = tail[1].parse().unwrap();
+
+        let table = std::str::from_utf8(&pdf[xref..]).unwrap();
+        let mut lines = table.lines();
+        assert_eq!(lines.next(), Some("xref"));
+        let count: usize = lines.next().unwrap().strip_prefix("0 ").unwrap().parse().unwrap();
+        assert_eq!(lines.next(), Some("0000000000 65535 f "));
+
+        let mut objects = vec![];
+        for number in 1..count {
+            let entry = lines.next().unwrap();
+            assert_eq!(entry.len(), 19, "xref entries are 20 bytes long with the newline");
+            let offset: usize = entry[..10].parse().unwrap();
+
+            let header = format!("{} 0 obj\n", number);
+            assert!(pdf[offset..].starts_with(header.as_bytes()), "object {} is not at {}", number, offset);
+            let start = offset + header.len();
+            let end = find(pdf, start, b"\nendobj\n").unwrap();
+            let object = std::str::from_utf8(&pdf[start..end]).unwrap().to_string();
+
+            if let Some(length) = object.strip_prefix("<< /Length ") {
+                let length: usize = length[..length.find(' ').unwrap()].parse().unwrap();
+                let stream = object.find("stream\n").unwrap() + "stream\n".len();
+                assert_eq!(&object[stream + length..], "endstream");
+            }
+
+            objects.push(object);
+        }
+        assert!(table.contains(&format!("trailer\n<< /Size {} ", count)));
+
+        objects
+    }
+
+    #[test]
+    fn string_literal_escapes_delimiters() {
+        assert_eq!(string_literal("Classifica"), "(Classifica)");
+        assert_eq!(string_literal("a (b) \\ c"), "(a \\(b\\) \\\\ c)");
+    }
+
+    #[test]
+    fn string_literal_encodes_win_ansi() {
+        assert_eq!(string_literal("Città è"), "(Citt\\340 \\350)");
+        assert_eq!(string_literal("€ “x” – …"), "(\\200 \\223x\\224 \\226 \\205)");
+        // Neither characters outside the encoding nor control characters can be shown
+        assert_eq!(string_literal("日本\n"), "(???)");
+    }
+
+    #[test]
+    fn fit_text_cuts_with_ellipsis() {
+        assert_eq!(fit_text("Liceo", Font::Regular, 10.0, 100.0), "Liceo");
+
+        let fitted = fit_text("Liceo scientifico statale Galileo Galilei", Font::Bold, 10.0, 80.0);
+        assert!(fitted.ends_with('…'));
+        assert!(Font::Bold.text_width(&fitted, 10.0) <= 80.0);
+    }
+
+    #[test]
+    fn render_round_trips() {
+        let mut first = Page::new(A4);
+        first.text_centered(800.0, Font::Bold, 20.0, "Classifica (finale)");
+        first.fill_rectangle(40.0, 700.0, 515.0, 20.0, 0.9);
+        let mut second = Page::landscape(A4);
+        second.text(50.0, 500.0, Font::Regular, 12.0, "Attestato di partecipazione: Città");
+
+        let pdf = render("Gara à", &[first, second]);
+        assert!(pdf.starts_with(b"%PDF-1.4\n"));
+
+        let objects = read_objects(&pdf);
+        assert_eq!(objects.len(), 5 + 2 * 2);
+        assert_eq!(objects[1], "<< /Type /Pages /Kids [6 0 R 8 0 R] /Count 2 >>");
+        assert!(objects[4].contains("/Title (Gara \\340)"));
+        assert!(objects[5].contains("/MediaBox [0 0 595.00 842.00]"));
+        assert!(objects[7].contains("/MediaBox [0 0 842.00 595.00]"));
+        assert!(objects[6].contains("(Classifica \\(finale\\)) Tj"));
+        assert!(objects[8].contains("(Attestato di partecipazione: Citt\\340) Tj"));
+    }
+
+    #[test]
+    fn render_without_pages() {
+        let objects = read_objects(&render("Vuoto", &[]));
+        assert_eq!(objects[1], "<< /Type /Pages /Kids [] /Count 0 >>");
+    }
+}
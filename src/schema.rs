@@ -3,8 +3,8 @@
 diesel::table! {
     contests (id) {
         id -> Int4,
-        phiquadro_id -> Int4,
-        phiquadro_sess -> Int4,
+        phiquadro_id -> Nullable<Int4>,
+        phiquadro_sess -> Nullable<Int4>,
         #[max_length = 255]
         contest_name -> Varchar,
         duration -> Int4,
@@ -18,6 +18,45 @@ diesel::table! {
         question_bonus -> Array<Nullable<Int4>>,
         contest_bonus -> Array<Nullable<Int4>>,
         owner_id -> Int4,
+        hidden_drift -> Bool,
+        time_scale -> Float8,
+        paused_at -> Nullable<Timestamptz>,
+        start_offset -> Int4,
+        leaderboard -> Bool,
+        leaderboard_hide_teams -> Bool,
+        leaderboard_hide_owner -> Bool,
+        freeze_time -> Nullable<Int4>,
+        revealed -> Bool,
+        #[max_length = 64]
+        spectator_token -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    final_ranking_teams (id) {
+        id -> Int4,
+        contest_id -> Int4,
+        rank -> Int4,
+        team_id -> Int4,
+        #[max_length = 255]
+        team_name -> Varchar,
+        is_fake -> Bool,
+        is_bot -> Bool,
+        is_ghost -> Bool,
+        phiquadro_sess -> Nullable<Int4>,
+        score -> Int4,
+        question_scores -> Array<Nullable<Int4>>,
+        question_statuses -> Array<Nullable<Int2>>,
+        jollies -> Array<Nullable<Bool>>,
+    }
+}
+
+diesel::table! {
+    final_rankings (contest_id) {
+        contest_id -> Int4,
+        computed_at -> Timestamptz,
+        question_scores -> Array<Nullable<Int4>>,
+        question_locked -> Array<Nullable<Bool>>,
     }
 }
 
@@ -27,6 +66,8 @@ diesel::table! {
         sub_time -> Timestamptz,
         team_id -> Int4,
         question_id -> Int4,
+        #[max_length = 255]
+        idempotency_key -> Nullable<Varchar>,
     }
 }
 
@@ -46,6 +87,8 @@ diesel::table! {
         sub_time -> Timestamptz,
         team_id -> Int4,
         question_id -> Int4,
+        #[max_length = 255]
+        idempotency_key -> Nullable<Varchar>,
     }
 }
 
@@ -57,6 +100,10 @@ diesel::table! {
         is_fake -> Bool,
         position -> Int4,
         contest_id -> Int4,
+        phiquadro_sess -> Nullable<Int4>,
+        hidden -> Bool,
+        is_bot -> Bool,
+        is_ghost -> Bool,
     }
 }
 
@@ -81,6 +128,8 @@ diesel::table! {
 }
 
 diesel::joinable!(contests -> users (owner_id));
+diesel::joinable!(final_ranking_teams -> final_rankings (contest_id));
+diesel::joinable!(final_rankings -> contests (contest_id));
 diesel::joinable!(jollies -> questions (question_id));
 diesel::joinable!(jollies -> teams (team_id));
 diesel::joinable!(questions -> contests (contest_id));
@@ -91,6 +140,8 @@ diesel::joinable!(tokens -> users (user_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
     contests,
+    final_ranking_teams,
+    final_rankings,
     jollies,
     questions,
     submissions,
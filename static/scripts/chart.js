@@ -0,0 +1,120 @@
+const CHART_WIDTH = 800;
+const CHART_HEIGHT = 400;
+const CHART_MARGIN = 40;
+const SVG_NS = "http://www.w3.org/2000/svg";
+
+var chart_visible = false;
+
+function toggle_chart() {
+    chart_visible = !chart_visible;
+    document.getElementById("chart-wrapper").style.display = chart_visible ? "" : "none";
+
+    if (chart_visible) {
+        load_chart();
+    }
+}
+
+function load_chart() {
+    if (!chart_visible) {
+        return;
+    }
+
+    fetch(`/api/contests/${contest_id()}/series`).then(res => res.json()).then(series => {
+        draw_chart(series, document.getElementById("chart-mode").value);
+    });
+}
+
+function svg_element(name, attributes) {
+    const element = document.createElementNS(SVG_NS, name);
+    for (const [key, value] of Object.entries(attributes)) {
+        element.setAttribute(key, value);
+    }
+
+    return element;
+}
+
+function draw_chart(series, mode) {
+    const chart = document.getElementById("chart");
+    chart.innerHTML = "";
+
+    if (series.times.length == 0) {
+        const text = svg_element("text", { x: CHART_WIDTH / 2, y: CHART_HEIGHT / 2, "text-anchor": "middle" });
+        text.textContent = "La gara non è ancora iniziata";
+        chart.appendChild(text);
+        return;
+    }
+
+    const values = series.teams.map(team => mode == "rank" ? team.ranks : team.scores);
+    const all = values.flat();
+    // ranks grow downwards, so the best team is always on top
+    const [min, max] = mode == "rank" ? [series.teams.length, 1] : [Math.min(...all), Math.max(...all)];
+    const last_time = Math.max(series.times[series.times.length - 1], 60);
+
+    const x = time => CHART_MARGIN + time / last_time * (CHART_WIDTH - 2 * CHART_MARGIN);
+    const y = value => {
+        return CHART_HEIGHT - CHART_MARGIN - (value - min) / ((max - min) || 1) * (CHART_HEIGHT - 2 * CHART_MARGIN);
+    };
+
+    chart.appendChild(svg_element("line", {
+        x1: CHART_MARGIN,
+        y1: CHART_HEIGHT - CHART_MARGIN,
+        x2: CHART_WIDTH - CHART_MARGIN,
+        y2: CHART_HEIGHT - CHART_MARGIN,
+        class: "chart-axis",
+    }));
+    chart.appendChild(svg_element("line", {
+        x1: CHART_MARGIN,
+        y1: CHART_MARGIN,
+        x2: CHART_MARGIN,
+        y2: CHART_HEIGHT - CHART_MARGIN,
+        class: "chart-axis",
+    }));
+
+    for (const [value, label] of [[min, min], [max, max]]) {
+        const text = svg_element("text", {
+            x: CHART_MARGIN - 5,
+            y: y(value) + 4,
+            "text-anchor": "end",
+            class: "chart-label",
+        });
+        text.textContent = label;
+        chart.appendChild(text);
+    }
+
+    const minutes = Math.floor(last_time / 60);
+    const minutes_step = Math.max(1, Math.ceil(minutes / 10 / 5) * 5);
+    for (let minute = 0; minute <= minutes; minute += minutes_step) {
+        const text = svg_element("text", {
+            x: x(minute * 60),
+            y: CHART_HEIGHT - CHART_MARGIN + 15,
+            "text-anchor": "middle",
+            class: "chart-label",
+        });
+        text.textContent = minute;
+        chart.appendChild(text);
+    }
+
+    const user_teams = series.teams.filter(team => !team.is_fake).length;
+    var user_index = 0;
+
+    // the fake teams are drawn first, so the teams of the users stay on top
+    const order = series.teams.map((team, i) => i).sort((a, b) => series.teams[b].is_fake - series.teams[a].is_fake);
+    for (const i of order) {
+        const team = series.teams[i];
+        const points = series.times.map((time, j) => `${x(time)},${y(values[i][j])}`).join(" ");
+
+        const line = svg_element("polyline", {
+            points: points,
+            class: team.is_fake ? "chart-fake-team" : "chart-real-team",
+        });
+        if (!team.is_fake) {
+            line.style.stroke = `hsl(${Math.round(360 * user_index / user_teams)}, 70%, 45%)`;
+            user_index++;
+        }
+
+        const title = svg_element("title", {});
+        title.textContent = `${team.team_name}: ${values[i][values[i].length - 1]}`;
+        line.appendChild(title);
+        chart.appendChild(line);
+    }
+}
@@ -8,18 +8,32 @@ window.onload = () => {
     setup_form(
         "contest-editor",
         (data) => {
-            return {
-                "phiquadro_id": parseInt(data.get("phiquadro_id")),
-                "phiquadro_sess": parseInt(data.get("phiquadro_sess")),
+            const contest = {
                 "name": data.get("name"),
                 "start_time": data.get("start_time"),
+                "start_offset": parseInt(data.get("start_offset")),
                 "duration": parseInt(data.get("duration")),
                 "drift": parseInt(data.get("drift")),
                 "drift_time": parseInt(data.get("drift_time")),
                 "jolly_time": parseInt(data.get("jolly_time")),
+                "time_scale": parseFloat(data.get("time_scale")),
+                "freeze_time": data.get("freeze_time") ? parseInt(data.get("freeze_time")) : null,
                 "question_bonus": Array(10).fill(0).map((_, i) => parseInt(data.get(`question_bonus_${i + 1}`))),
                 "contest_bonus": Array(10).fill(0).map((_, i) => parseInt(data.get(`contest_bonus_${i + 1}`))),
+                "team_selection": conv_selection(data),
+                "count_hidden_for_drift": data.get("count_for_drift") == "on",
             };
+
+            if (data.get("source") == "custom") {
+                const questions_no = parseInt(data.get("questions_no"));
+                contest["answers"] = Array(questions_no).fill(0).map((_, i) => parseInt(data.get(`answer_${i + 1}`)));
+                contest["ghost_teams"] = Array.from(document.getElementsByClassName("ghost-team")).map(conv_ghost_team);
+            } else {
+                contest["phiquadro_id"] = parseInt(data.get("phiquadro_id"));
+                contest["phiquadro_sess"] = data.get("phiquadro_sess").split(",").map(sess => parseInt(sess));
+            }
+
+            return contest;
         },
         (response) => {
             if (response.status == 201) {
@@ -38,4 +52,77 @@ window.onload = () => {
     const now = new Date();
     const datetime = new Date(now.getTime() + 300000); // five minutes from now
     document.getElementById("start_time").setAttribute("value", `${datetime.getFullYear()}-${pad(datetime.getMonth() + 1)}-${pad(datetime.getDate())} ${pad(datetime.getHours())}:${pad(datetime.getMinutes())}`);
+
+    update_source();
+    update_answers();
+    update_selection();
 };
+
+function update_source() {
+    const custom = document.getElementById("source").value == "custom";
+
+    Array.from(document.getElementsByClassName("phiquadro-field")).forEach(elem => {
+        elem.hidden = custom;
+        elem.querySelectorAll("input").forEach(input => input.required = !custom);
+    });
+
+    Array.from(document.getElementsByClassName("custom-field")).forEach(elem => {
+        elem.hidden = !custom;
+        elem.querySelectorAll("input:not(.optional)").forEach(input => input.required = custom);
+    });
+
+    document.getElementById("name").placeholder = custom ? "" : "se bianco, è il nome della gara";
+}
+
+function update_answers() {
+    const answers = document.getElementById("answers");
+    const questions_no = Math.max(0, Math.min(100, parseInt(document.getElementById("questions_no").value) || 0));
+    const custom = document.getElementById("source").value == "custom";
+
+    while (answers.children.length > questions_no) {
+        answers.removeChild(answers.lastChild);
+    }
+
+    while (answers.children.length < questions_no) {
+        const i = answers.children.length + 1;
+        const input = document.createElement("input");
+        input.name = `answer_${i}`;
+        input.id = `answer_${i}`;
+        input.type = "number";
+        input.placeholder = `${i}`;
+        input.title = `Risposta al quesito ${i}`;
+        input.required = custom;
+        answers.appendChild(input);
+    }
+}
+
+function add_ghost_team() {
+    const team = document.createElement("div");
+    team.className = "ghost-team";
+    team.innerHTML = `
+        <div>
+          <input class="ghost-team-name" type="text" placeholder="Nome della squadra" required>
+          <div class="action-button" title="Rimuovi" onclick="this.parentElement.parentElement.remove();">
+            <img class="icon" src="/icons/bin.svg" height="20">
+          </div>
+        </div>
+        <input class="ghost-team-jolly optional" type="number" min="1" placeholder="Quesito scelto come jolly">
+        <textarea class="ghost-team-submissions" placeholder="Una risposta per riga: minuto, quesito, risposta"></textarea>
+    `;
+
+    document.getElementById("ghost-teams").insertBefore(team, document.getElementById("add-ghost-team"));
+}
+
+function conv_ghost_team(team) {
+    const jolly = parseInt(team.querySelector(".ghost-team-jolly").value);
+
+    return {
+        "name": team.querySelector(".ghost-team-name").value,
+        "jolly": isNaN(jolly) ? null : jolly,
+        "submissions": team.querySelector(".ghost-team-submissions").value
+            .split("\n")
+            .map(line => line.split(/[\s,;]+/).filter(field => field != "").map(field => parseInt(field)))
+            .filter(fields => fields.length == 3)
+            .map(([minute, question, answer]) => ({ "minute": minute, "question": question, "answer": answer })),
+    };
+}
@@ -0,0 +1,108 @@
+window.onload = () => {
+    sync_clock();
+    show_page();
+
+    window.addEventListener("resize", show_page);
+};
+
+setInterval(reload_content, 15000); // reload ranking every 15 seconds
+setInterval(update_clock, 1000);
+setInterval(next_page, 10000); // show the next teams every 10 seconds
+
+var page = 0;
+var clock_state = null;
+var clock_end = null; // when the clock reaches zero, in milliseconds
+var clock_expired = false;
+
+// the clock is set from the seconds left when the page was loaded, so that the time of the projector doesn't matter
+function sync_clock() {
+    const display = document.getElementById("display");
+    clock_state = display.dataset.state;
+    clock_end = new Date().getTime() + 1000 * parseInt(display.dataset.seconds);
+    clock_expired = false;
+
+    update_clock();
+}
+
+function format_time(seconds) {
+    const hours = Math.floor(seconds / 3600);
+    const minutes = String(Math.floor(seconds / 60) % 60).padStart(2, "0");
+    const secs = String(seconds % 60).padStart(2, "0");
+
+    return hours > 0 ? `${hours}:${minutes}:${secs}` : `${minutes}:${secs}`;
+}
+
+function update_clock() {
+    const states = {
+        not_started: "La gara inizia tra",
+        running: "Tempo rimanente",
+        paused: "La gara è in pausa",
+        over: "La gara è terminata",
+    };
+    document.getElementById("clock-state").innerHTML = states[clock_state];
+
+    if (clock_state == "over") {
+        document.getElementById("clock-time").innerHTML = "";
+        return;
+    }
+
+    var seconds = Math.ceil((clock_end - new Date().getTime()) / 1000);
+    if (clock_state == "paused") {
+        seconds = parseInt(document.getElementById("display").dataset.seconds);
+    } else if (seconds <= 0) {
+        // the contest just started or ended
+        seconds = 0;
+        if (!clock_expired) {
+            clock_expired = true;
+            reload_content();
+        }
+    }
+
+    document.getElementById("clock-time").innerHTML = format_time(seconds);
+}
+
+function reload_content() {
+    fetch(window.location.href).then(body => body.text()).then(text => {
+        const parser = new DOMParser();
+        const doc = parser.parseFromString(text, "text/html");
+
+        document.getElementById("display").outerHTML = doc.getElementById("display").outerHTML;
+
+        sync_clock();
+        show_page();
+    })
+}
+
+// How many teams fit in the screen below the questions
+function page_size() {
+    const table = document.getElementById("display-ranking");
+    const rows = table.querySelectorAll("tr");
+    if (rows.length == 0) {
+        return 1;
+    }
+
+    rows.forEach(row => row.style.display = "");
+    const row_height = rows[0].getBoundingClientRect().height;
+    const footer = document.getElementById("display-page").getBoundingClientRect().height;
+    const available = window.innerHeight - table.getBoundingClientRect().top - 2 * footer;
+
+    return Math.max(1, Math.floor(available / row_height));
+}
+
+function show_page() {
+    const rows = document.querySelectorAll("#display-ranking tr");
+    const size = page_size();
+    const pages = Math.max(1, Math.ceil(rows.length / size));
+    page = page % pages;
+
+    rows.forEach((row, i) => {
+        row.style.display = Math.floor(i / size) == page ? "" : "none";
+    });
+
+    document.getElementById("display-page").innerHTML = pages > 1 ? `Pagina ${page + 1} di ${pages}` : "";
+}
+
+function next_page() {
+    page++;
+    show_page();
+}
@@ -1,23 +1,31 @@
-function send_form(form, conv, callback) {
+function send_form(form, conv, callback, headers) {
     const form_data = new FormData(form);
+    const body = conv(form_data);
 
     fetch(form.getAttribute("action"), {
         method: form.getAttribute("method"),
-        body: JSON.stringify(conv(form_data)),
+        body: JSON.stringify(body),
         headers: {
             "Content-Type": "application/json",
+            ...(headers ? headers(body) : {}),
         }
     }).then(callback);
 }
 
-function setup_form(id, conv, callback) {
+// A random key for the Idempotency-Key header; crypto.randomUUID is not used as it needs HTTPS
+function new_idempotency_key() {
+    const bytes = crypto.getRandomValues(new Uint8Array(16));
+    return Array.from(bytes, byte => byte.toString(16).padStart(2, "0")).join("");
+}
+
+function setup_form(id, conv, callback, headers) {
     var form = document.getElementById(id);
 
     if (!form)
         return;
 
     form.onsubmit = (event) => {
-        send_form(form, conv, callback);
+        send_form(form, conv, callback, headers);
 
         return false;
     };
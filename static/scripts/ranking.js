@@ -4,34 +4,92 @@ window.onload = () => {
 
     document.addEventListener('fullscreenchange', exit_fullscreen_adjust, false);
 
-    setup_flipdown();
+    setup_flipdown(true);
 };
 
 setInterval(reload_content, 15000); // reload ranking every 15 seconds
 
-function setup_flipdown() {
-    document.getElementById("flipdown").innerHTML = "";
-    document.getElementById("clock-text").innerHTML = "";
+const REVEAL_DELAY = 800; // milliseconds between the teams revealed
 
-    const url = window.location.href.split("/");
-    const id = url[url.length - 1];
+var flipdown = null;
+var flipdown_state = null;
+var revealing = false;
 
-    fetch(`/api/contests/${id}`).then(res => res.json()).then(res => {
-        const start_date = new Date(`${res.start_time}Z`).getTime();
+function contest_id() {
+    const url = window.location.pathname.split("/");
+    return url[url.length - 1];
+}
+
+function setup_flipdown(force) {
+    fetch(`/api/contests/${contest_id()}`).then(res => res.json()).then(res => {
+        // the clock is only rebuilt when the contest is paused, resumed or rescheduled
+        const state = `${res.start_time} ${res.paused_at} ${res.duration} ${res.time_scale} ${res.start_offset}`;
+        if (!force && state == flipdown_state) {
+            return;
+        }
+        flipdown_state = state;
+
+        if (flipdown) {
+            clearInterval(flipdown.countdown);
+        }
+
+        document.getElementById("flipdown").innerHTML = "";
+        document.getElementById("flipdown").style.display = "";
+        document.getElementById("clock-text").innerHTML = "";
+        document.getElementById("pause-button").style.display = "none";
+        document.getElementById("resume-button").style.display = "none";
 
-        if (start_date > new Date().getTime()) {
-            var flipdown = new FlipDown(start_date / 1000).start().ifEnded(setup_flipdown);
+        const start_date = new Date(`${res.start_time}Z`).getTime();
+        // the start is when the user teams join, which can be later than the start of the replay
+        const duration = (res.duration - res.start_offset) / res.time_scale;
+
+        if (res.paused_at) {
+            // the clock is stopped at the time left when the contest was paused
+            const paused_date = new Date(`${res.paused_at}Z`).getTime();
+            flipdown = new FlipDown(new Date().getTime() / 1000 + duration - (paused_date - start_date) / 1000).start();
+            clearInterval(flipdown.countdown);
+            document.getElementById("clock-text").innerHTML = "La gara è in pausa";
+            document.getElementById("resume-button").style.display = "flex";
+        } else if (start_date > new Date().getTime()) {
+            flipdown = new FlipDown(start_date / 1000).start().ifEnded(() => setup_flipdown(true));
             document.getElementById("clock-text").innerHTML = "La gara non è ancora iniziata";
-        } else if (start_date + 1000 * res.duration < new Date().getTime()) {
+        } else if (start_date + 1000 * duration < new Date().getTime()) {
             document.getElementById("clock-text").innerHTML = "La gara è terminata";
             document.getElementById("flipdown").style.display = "none";
         } else {
-            var flipdown = new FlipDown(start_date / 1000 + res.duration).start().ifEnded(setup_flipdown);
+            flipdown = new FlipDown(start_date / 1000 + duration).start().ifEnded(() => setup_flipdown(true));
+            document.getElementById("pause-button").style.display = "flex";
         }
     });
 }
 
+function pause_contest() {
+    fetch(`/api/contests/${contest_id()}/pause`, { method: "POST" }).then(clock_callback);
+}
+
+function resume_contest() {
+    fetch(`/api/contests/${contest_id()}/resume`, { method: "POST" }).then(clock_callback);
+}
+
+function clock_callback(response) {
+    if (response.status == 204) {
+        setup_flipdown(true);
+        reload_content();
+    } else {
+        response.json().then(body => {
+            alert(body.error)
+        });
+    }
+}
+
 function reload_content() {
+    if (revealing) {
+        return;
+    }
+
+    setup_flipdown(false);
+    load_chart();
+
     const hidden_teams = document.getElementById("toggle-visibility").getAttribute("onclick") == "show_fake_teams()";
 
     fetch(window.location.href).then(body => body.text()).then(text => {
@@ -48,6 +106,76 @@ function reload_content() {
     })
 }
 
+async function fetch_ranking(url) {
+    const text = await fetch(url).then(body => body.text());
+
+    return new DOMParser().parseFromString(text, "text/html").getElementById("ranking");
+}
+
+async function reveal_ranking() {
+    if (!confirm("La classifica in tempo reale verrà mostrata agli spettatori. Sei sicuro?")) {
+        return;
+    }
+
+    // the frozen ranking can't be seen anymore after the reveal
+    const frozen = await fetch_ranking(`/contest/${contest_id()}?frozen=true`);
+
+    const response = await fetch(`/api/contests/${contest_id()}/reveal`, { method: "POST" });
+    if (response.status != 204) {
+        response.json().then(body => {
+            alert(body.error)
+        });
+        return;
+    }
+
+    const live = await fetch_ranking(`/contest/${contest_id()}`);
+    document.getElementById("freeze-banner").style.display = "none";
+    animate_reveal(frozen, live);
+}
+
+// Starting from the frozen ranking, the teams are moved to their live position one at a time, from the last one
+function animate_reveal(frozen, live) {
+    revealing = true;
+
+    const hidden_teams = document.getElementById("toggle-visibility").getAttribute("onclick") == "show_fake_teams()";
+    const table = document.getElementById("ranking");
+    table.innerHTML = frozen.innerHTML;
+    const body = table.querySelector("tbody") || table;
+
+    const rows = Array.from(live.querySelectorAll("tr[data-team]")).reverse();
+    var first_revealed = null;
+
+    const step = () => {
+        const row = rows.shift();
+        if (!row) {
+            revealing = false;
+            if (window.location.search) {
+                window.location.href = `/contest/${contest_id()}`;
+            }
+            return;
+        }
+
+        const old = body.querySelector(`tr[data-team="${row.dataset.team}"]`);
+        const changed = !old || old.innerHTML != row.innerHTML || old.nextElementSibling != first_revealed;
+        if (old) {
+            old.remove();
+        }
+
+        if (changed) {
+            row.classList.add("revealed-team");
+        }
+        if (hidden_teams && row.classList.contains("fake-team")) {
+            row.setAttribute("hidden", "");
+        }
+        body.insertBefore(row, first_revealed);
+        first_revealed = row;
+
+        setTimeout(step, changed && !row.hasAttribute("hidden") ? REVEAL_DELAY : 0);
+    };
+
+    step();
+}
+
 function hide_fake_teams() {
     Array.from(document.getElementsByClassName("fake-team")).forEach(elem => {
         elem.setAttribute("hidden", "");
@@ -0,0 +1,166 @@
+window.onload = () => {
+    load_header();
+
+    const entry = document.getElementById("entry");
+    entry.addEventListener("keydown", (event) => {
+        if (event.key == "Enter") {
+            event.preventDefault();
+            submit_entry();
+        } else if (event.key == "z" && (event.ctrlKey || event.metaKey)) {
+            event.preventDefault();
+            undo_entry();
+        }
+    });
+    entry.focus();
+};
+
+var sending = false;
+var undo_stack = []; // the entries which can still be taken back, the last one at the end
+var pending_entry = null; // the line being sent and its key, kept until accepted so that it is never counted twice
+
+function contest_id() {
+    return document.getElementById("referee").dataset.contest;
+}
+
+function show_feedback(kind, text) {
+    const feedback = document.getElementById("feedback");
+    feedback.className = `feedback-${kind}`;
+    feedback.innerText = text;
+}
+
+// Reads a line like "3 12 4521" into the team, the question and the answer, optionally followed by how many minutes
+// ago the answer was handed in
+function parse_entry(line) {
+    const fields = line.trim().split(/\s+/);
+    if (fields.length < 3 || fields.length > 4 || fields.some(field => !/^-?\d+$/.test(field))) {
+        return { error: "Scrivi tre numeri: squadra, quesito e risposta, più i minuti di ritardo se ce ne sono" };
+    }
+
+    const [team_no, question_no, answer, delay] = fields.map(field => parseInt(field));
+    if (delay < 0) {
+        return { error: "Il ritardo non può essere negativo" };
+    }
+
+    const team = document.querySelector(`#referee-teams li:nth-child(${team_no})`);
+    if (team_no < 1 || !team) {
+        return { error: `La squadra ${team_no} non esiste` };
+    }
+
+    const questions = document.getElementById("referee").dataset.questions.split(",");
+    if (question_no < 1 || question_no > questions.length) {
+        return { error: `Il quesito ${question_no} non esiste` };
+    }
+
+    return {
+        team_no,
+        team_id: parseInt(team.dataset.team),
+        team_name: team.innerText,
+        question_no,
+        question_id: parseInt(questions[question_no - 1]),
+        answer,
+        delay: delay || 0,
+    };
+}
+
+function submit_entry() {
+    if (sending) {
+        return;
+    }
+
+    const input = document.getElementById("entry");
+    const entry = parse_entry(input.value);
+    if (entry.error) {
+        show_feedback("error", entry.error);
+        return;
+    }
+
+    const line = input.value.trim();
+    if (!pending_entry || pending_entry.line != line) {
+        pending_entry = { line, key: new_idempotency_key() };
+    }
+
+    sending = true;
+    fetch(`/api/contests/${contest_id()}/submissions`, {
+        method: "POST",
+        body: JSON.stringify({
+            "team_id": entry.team_id,
+            "question_id": entry.question_id,
+            "answer": entry.answer,
+            "sub_time": entry.delay > 0 ? new Date(Date.now() - 60000 * entry.delay).toISOString() : null,
+        }),
+        headers: {
+            "Content-Type": "application/json",
+            "Idempotency-Key": pending_entry.key,
+        }
+    }).then((response) => {
+        return response.json().then(body => {
+            if (response.status != 201) {
+                show_feedback("error", body.error);
+                return;
+            }
+
+            pending_entry = null;
+
+            entry.submission_id = body.submission_id;
+            entry.correct = body.correct;
+            entry.duplicate = body.duplicate;
+            entry.row = add_row(entry);
+            undo_stack.push(entry);
+            document.getElementById("undo-button").disabled = false;
+
+            const result = `${entry.team_name}, quesito ${entry.question_no}: risposta ${entry.correct ? "esatta" : "errata"}`;
+            if (entry.duplicate) {
+                show_feedback("duplicate", `${result}. La squadra aveva già dato questa risposta!`);
+            } else {
+                show_feedback(entry.correct ? "correct" : "wrong", result);
+            }
+            input.value = "";
+        });
+    }).finally(() => {
+        sending = false;
+        input.focus();
+    });
+}
+
+function add_row(entry) {
+    const table = document.getElementById("entries");
+    const row = table.insertRow(1);
+    row.className = entry.duplicate ? "entry-duplicate" : entry.correct ? "entry-correct" : "entry-wrong";
+
+    const outcome = (entry.correct ? "Esatta" : "Errata") + (entry.duplicate ? " (doppia)" : "");
+    const cells = [
+        new Date(Date.now() - 60000 * entry.delay).toLocaleTimeString("it-IT"),
+        `${entry.team_no}. ${entry.team_name}`,
+        entry.question_no,
+        entry.answer,
+        outcome,
+    ];
+    cells.forEach(text => {
+        row.insertCell().innerText = text;
+    });
+
+    return row;
+}
+
+function undo_entry() {
+    const entry = undo_stack[undo_stack.length - 1];
+    if (!entry || sending) {
+        return;
+    }
+
+    sending = true;
+    fetch(`/api/contests/${contest_id()}/submissions/${entry.submission_id}`, { method: "DELETE" }).then((response) => {
+        if (response.status == 204) {
+            undo_stack.pop();
+            entry.row.classList.add("entry-undone");
+            entry.row.cells[4].innerText = "Annullata";
+            show_feedback("undone", `Annullata la risposta ${entry.answer} della squadra ${entry.team_name} al quesito ${entry.question_no}`);
+        } else {
+            response.json().then(body => show_feedback("error", body.error));
+        }
+        document.getElementById("undo-button").disabled = undo_stack.length == 0;
+    }).finally(() => {
+        sending = false;
+        document.getElementById("entry").focus();
+    });
+}
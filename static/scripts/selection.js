@@ -0,0 +1,27 @@
+function update_selection() {
+    const mode = document.getElementById("selection_mode").value;
+
+    Array.from(document.getElementsByClassName("selection-field")).forEach(elem => {
+        elem.hidden = !elem.classList.contains(`selection-${mode}`);
+    });
+}
+
+function conv_selection(data) {
+    const selection = { "mode": data.get("selection_mode") };
+
+    switch (selection["mode"]) {
+        case "rank":
+            selection["rank"] = parseInt(data.get("selection_rank"));
+            break;
+        case "name":
+            selection["pattern"] = data.get("selection_pattern");
+            break;
+        case "teams":
+            selection["teams"] = data.has("selection_teams")
+                ? data.get("selection_teams").split("\n").map(name => name.trim()).filter(name => name != "")
+                : data.getAll("selection_team");
+            break;
+    }
+
+    return selection;
+}
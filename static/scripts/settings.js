@@ -7,10 +7,13 @@ window.onload = () => {
         (data) => {
             return {
                 "start_time": data.get("start_time"),
+                "start_offset": parseInt(data.get("start_offset")),
                 "duration": parseInt(data.get("duration")),
                 "drift": parseInt(data.get("drift")),
                 "drift_time": parseInt(data.get("drift_time")),
                 "jolly_time": parseInt(data.get("jolly_time")),
+                "time_scale": parseFloat(data.get("time_scale")),
+                "freeze_time": data.get("freeze_time") ? parseInt(data.get("freeze_time")) : null,
                 "question_bonus": Array(10).fill(0).map((_, i) => parseInt(data.get(`question_bonus_${i + 1}`))),
                 "contest_bonus": Array(10).fill(0).map((_, i) => parseInt(data.get(`contest_bonus_${i + 1}`))),
             };
@@ -25,6 +28,87 @@ window.onload = () => {
             }
         },
     );
+
+    setup_form(
+        "team-selection",
+        (data) => {
+            return {
+                ...conv_selection(data),
+                "count_for_drift": data.get("count_for_drift") == "on",
+            };
+        },
+        (response) => {
+            if (response.status == 200) {
+                response.json().then(body => {
+                    alert(`Squadre selezionate: ${body.selected}`);
+                    window.location.reload();
+                });
+            } else {
+                response.json().then(body => {
+                    alert(body.error)
+                });
+            }
+        },
+    );
+
+    setup_form(
+        "add-bots",
+        conv_add_bots,
+        (response) => {
+            if (response.status == 201) {
+                response.json().then(body => {
+                    alert(`Bot aggiunti con il seme ${body.seed}`);
+                    window.location.reload();
+                });
+            } else {
+                response.json().then(body => {
+                    alert(body.error)
+                });
+            }
+        },
+    );
+
+    setup_form("add-ghost", conv_add_ghost, reload_callback);
+
+    ["spectator_link", "display_link"].forEach(id => {
+        const link = document.getElementById(id);
+        if (link) {
+            link.value = window.location.origin + link.value;
+        }
+    });
+
+    setup_form(
+        "leaderboard-settings",
+        (data) => {
+            return {
+                "opt_in": data.get("opt_in") == "on",
+                "hide_teams": data.get("hide_teams") == "on",
+                "hide_owner": data.get("hide_owner") == "on",
+            };
+        },
+        (response) => {
+            if (response.status == 204) {
+                alert("Impostazioni salvate");
+            } else {
+                response.json().then(body => {
+                    alert(body.error)
+                });
+            }
+        },
+    );
+
+    const restart_form = document.getElementById("contest-restart");
+    restart_form.onsubmit = () => {
+        if (confirm("Le risposte e i jolly delle squadre aggiunte verranno cancellati! Sei sicuro?")) {
+            send_form(restart_form, conv_restart, restart_callback);
+        }
+
+        return false;
+    };
+
+    if (document.getElementById("selection_mode")) {
+        update_selection();
+    }
 };
 
 async function delete_team(contest_id, id) {
@@ -45,6 +129,49 @@ async function delete_team(contest_id, id) {
     })
 }
 
+function recompute_final_ranking(contest_id) {
+    if (!confirm("La classifica finale salvata verrà sostituita! Sei sicuro?")) {
+        return;
+    }
+
+    fetch(`/api/contests/${contest_id}/final_ranking`, {
+        method: "POST",
+    }).then(response => {
+        if (response.status == 204) {
+            alert("Classifica finale ricalcolata");
+            window.location.reload();
+        } else {
+            response.json().then(body => {
+                alert(body.error)
+            });
+        }
+    })
+}
+
+function create_spectator_link(contest_id) {
+    fetch(`/api/contests/${contest_id}/spectator`, {
+        method: "POST",
+    }).then(reload_callback);
+}
+
+function delete_spectator_link(contest_id) {
+    if (!confirm("Il link attuale smetterà di funzionare! Sei sicuro?")) {
+        return;
+    }
+
+    fetch(`/api/contests/${contest_id}/spectator`, {
+        method: "DELETE",
+    }).then(response => {
+        if (response.status == 204) {
+            window.location.reload();
+        } else {
+            response.json().then(body => {
+                alert(body.error)
+            });
+        }
+    })
+}
+
 function reload_callback(response) {
     console.log(response);
     if (response.status == 201) {
@@ -61,3 +188,39 @@ function conv_add_team(data) {
         "team_name": data.get("team_name"),
     };
 }
+
+function conv_add_bots(data) {
+    return {
+        "percentile": parseInt(data.get("percentile")),
+        "count": parseInt(data.get("count")),
+        "name": data.get("name") || null,
+        "seed": data.get("seed") ? parseInt(data.get("seed")) : null,
+    };
+}
+
+function conv_add_ghost(data) {
+    const [source_contest, team_id] = data.get("source").split(":").map(x => parseInt(x));
+
+    return {
+        "source_contest": source_contest,
+        "team_id": team_id,
+        "team_name": data.get("team_name") || null,
+    };
+}
+
+function conv_restart(data) {
+    return {
+        "start_time": data.get("start_time"),
+        "keep_teams": data.get("keep_teams") == "on",
+    };
+}
+
+function restart_callback(response) {
+    if (response.status == 204) {
+        window.location.reload();
+    } else {
+        response.json().then(body => {
+            alert(body.error)
+        });
+    }
+}
@@ -0,0 +1,16 @@
+window.onload = () => {
+    load_header();
+};
+
+setInterval(reload_content, 15000); // reload ranking every 15 seconds
+
+function reload_content() {
+    fetch(window.location.href).then(body => body.text()).then(text => {
+        const parser = new DOMParser();
+        const doc = parser.parseFromString(text, "text/html");
+
+        ["freeze-banner", "ranking"].forEach(id => {
+            document.getElementById(id).outerHTML = doc.getElementById(id).outerHTML;
+        });
+    })
+}
@@ -1,3 +1,22 @@
+// The same request sent again, for example after a network error, keeps its key so that it is not counted twice
+var pending_request = null;
+var pending_key = null;
+
+function idempotency_headers(body) {
+    const request = JSON.stringify(body);
+    if (request != pending_request) {
+        pending_request = request;
+        pending_key = new_idempotency_key();
+    }
+
+    return { "Idempotency-Key": pending_key };
+}
+
+function forget_pending_request() {
+    pending_request = null;
+    pending_key = null;
+}
+
 function setup_submitter() {
     setup_form(
         "submitter",
@@ -10,6 +29,7 @@ function setup_submitter() {
         },
         (response) => {
             if (response.status == 201) {
+                forget_pending_request();
                 response.json().then(body => {
                     if (body.correct) {
                         alert("Risposta esatta!");
@@ -26,6 +46,7 @@ function setup_submitter() {
                 });
             }
         },
+        idempotency_headers,
     );
 }
 
@@ -33,17 +54,21 @@ function submit_jolly(contest_id) {
     const form = document.getElementById("submitter");
     const form_data = new FormData(form);
 
+    const body = {
+        "team_id": parseInt(form_data.get("team_id")),
+        "question_id": parseInt(form_data.get("question_id")),
+    };
+
     fetch(`/api/contests/${contest_id}/jollies`, {
         method: "POST",
-        body: JSON.stringify({
-            "team_id": parseInt(form_data.get("team_id")),
-            "question_id": parseInt(form_data.get("question_id")),
-        }),
+        body: JSON.stringify(body),
         headers: {
             "Content-Type": "application/json",
+            ...idempotency_headers(body),
         }
     }).then((response) => {
         if (response.status == 201) {
+            forget_pending_request();
             alert("Jolly scelto!");
             document.getElementById("submitter").reset();
             if (typeof reload_content !== "undefined") reload_content();